@@ -21,7 +21,11 @@ use crate::{
         Now, TIME_FORMAT,
     },
     cloneable,
-    display::{Error as DisplayError, PartialUpdate, Pos, RenderPipeline},
+    display::{
+        panel::{Panel, Rotation},
+        raster::{cell_width, line_height},
+        DisplayBackend, Error as DisplayError, PartialUpdate, Pos,
+    },
     err,
     formatter::{self, Dims, GlyphXCnt, GlyphYCnt, LeftFormatter},
     stm,
@@ -32,43 +36,44 @@ use DisplayMachine::*;
 use chrono::prelude::*;
 use core::{cmp::Ordering, fmt::Debug};
 use log::{trace,error};
-use std::iter::from_fn;
+use serde::Deserialize;
+use std::iter::{from_fn, once};
 
 const HEADING_ID: &str = "heading";
 const PULSE_ID: &str = "pulse";
 const EMAIL_ID: &str = "email";
 const EVENTS_ID: &str = "events";
 
-const HEADING_POS: Pos = Pos(10, 0);
-const PULSE_POS: Pos = Pos(0, 0);
-const EMAIL_POS: Pos = Pos(96, 4);
-const EVENTS_POS: Pos = Pos(0, 20);
-const INSTR1_POS: Pos = Pos(24, 24);
-const CODE_POS: Pos = Pos(64, 48);
-const INSTR2_POS: Pos = Pos(20, 108);
-const EXPIRY_POS: Pos = Pos(82, 122);
-
-const LARGE_SIZE: u32 = 24;
-const SMALL_SIZE: u32 = 12;
-const INSTR_SIZE: u32 = 16;
-const HEADING_SIZE: u32 = 16;
-const PULSE_SIZE: u32 = 16;
-const EMAIL_SIZE: u32 = 10;
-const EVENTS_SIZE: u32 = 16;
+const ERROR_HEADING: &str = "Error";
+const ERROR_LINE_SPACING: i32 = 2;
 
 const DATE_FORMAT: &str = "%e %b";
+const WEEK_DAY_FORMAT: &str = "%a %e %b";
+const MONTH_FORMAT: &str = "%b %Y";
+const WEEKDAY_INITIALS: [&str; 7] = ["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su"];
+//a day with more events than there are markers gets the last of them
+const BUSY_MARKERS: [char; 5] = [' ', '.', ':', '*', '#'];
+const UNREAD_MARKER: char = '?';
+const SELECTED_MARKER: char = '<';
+const NOW_PREFIX: &str = "Now: ";
+const FREE_NOW: &str = "Free now";
+const NOTHING_NEXT: &str = "Nothing next";
 const NO_EVENTS: &str = "No events";
 const NO_EMAIL: &str = "E-mail not listed";
+const SAVED_FORMAT: &str = "Offline. Saved %e %b %H:%M";
+const NOT_SAVED: &str = "Offline. Not saved";
+const NOT_READ: &str = "Not read yet";
 const END_DELIMITER: &str = " ";
 const IN_PROGRESS_DELIMITER: &str = "<";
 
 const STATUS_FLASH_OFF: &str = " ";
 
-stm!(machine display_stm, DisplayMachine, DisplayAtEnd, DisplayTerminals, [Empty, SaveWarning, UserCode, Events] => Unknown() |end|, {
-    [Empty, UserCode, Events, Unknown] => SaveWarning() |end|;
-    [Empty, SaveWarning, Events, Unknown] => UserCode()  |end|;
-    [Empty, SaveWarning, UserCode, Unknown] => Events() |end|;
-    [SaveWarning, UserCode, Events, Unknown] => Empty() |end|;
+stm!(machine display_stm, DisplayMachine, DisplayAtEnd, DisplayTerminals, [Empty, SaveWarning, UserCode, Events, Message] => Unknown() |end|, {
+    [Empty, UserCode, Events, Message, Unknown] => SaveWarning() |end|;
+    [Empty, SaveWarning, Events, Message, Unknown] => UserCode()  |end|;
+    [Empty, SaveWarning, UserCode, Message, Unknown] => Events() |end|;
+    [Empty, SaveWarning, UserCode, Events, Unknown] => Message() |end|;
+    [SaveWarning, UserCode, Events, Message, Unknown] => Empty() |end|;
 });
 
 stm!(machine app_stm, AppMachine, AppAtEnd, AppTerminals,
@@ -90,7 +95,258 @@ err!(Error {
     ArgumentOutOfRange(ArgumentOutOfRange)
 });
 
-const SCREEN_DIMS: Dims = Dims(GlyphXCnt(26), GlyphYCnt(9));
+/// Where a piece of text is drawn and the size of its font.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Placement {
+    pub x: u32,
+    pub y: i32,
+    pub size: u32,
+}
+
+impl Placement {
+    fn pos(&self) -> Pos {
+        Pos(self.x, self.y)
+    }
+}
+
+/// The arrangement of every screen on one size of panel. The columns
+/// and rows are the number of event characters and lines that fit on
+/// the events screen.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layout {
+    pub columns: usize,
+    pub rows: usize,
+    pub heading: Placement,
+    pub pulse: Placement,
+    pub email: Placement,
+    pub events: Placement,
+    /// Above the user code, also used for the heading of the error screen.
+    pub instructions: Placement,
+    pub code: Placement,
+    pub url: Placement,
+    pub expiry: Placement,
+    pub saving: Placement,
+    pub warning: Placement,
+    /// The first line of an error message, with the rest below it.
+    pub error: Placement,
+    pub error_columns: usize,
+    pub error_rows: usize,
+}
+
+impl Default for Layout {
+    fn default() -> Layout {
+        Layout::profile(Panel::default(), Rotation::None)
+    }
+}
+
+impl Layout {
+    /// The layout that suits panel turned by rotation, before any
+    /// changes in the settings.
+    pub fn profile(panel: Panel, rotation: Rotation) -> Layout {
+        let place = |x, y, size| Placement { x, y, size };
+        if rotation.portrait() {
+            return Layout::portrait_profile(panel);
+        }
+        match panel {
+            Panel::Small => Layout {
+                columns: 21,
+                rows: 8,
+                heading: place(6, 0, 10),
+                pulse: place(0, 0, 10),
+                email: place(48, 2, 6),
+                events: place(0, 12, 10),
+                instructions: place(4, 4, 8),
+                code: place(16, 24, 18),
+                url: place(2, 60, 6),
+                expiry: place(30, 74, 8),
+                saving: place(29, 24, 18),
+                warning: place(28, 60, 6),
+                error: place(2, 18, 6),
+                error_columns: 40,
+                error_rows: 9,
+            },
+            Panel::Medium => Layout {
+                columns: 24,
+                rows: 11,
+                heading: place(8, 0, 12),
+                pulse: place(0, 0, 12),
+                email: place(56, 2, 8),
+                events: place(0, 14, 10),
+                instructions: place(2, 8, 10),
+                code: place(20, 36, 20),
+                url: place(2, 80, 8),
+                expiry: place(30, 96, 8),
+                saving: place(30, 36, 20),
+                warning: place(24, 80, 8),
+                error: place(2, 26, 8),
+                error_columns: 34,
+                error_rows: 10,
+            },
+            Panel::Wide => Layout {
+                columns: 26,
+                rows: 6,
+                heading: place(8, 0, 12),
+                pulse: place(0, 0, 12),
+                email: place(72, 2, 8),
+                events: place(0, 14, 12),
+                instructions: place(8, 4, 12),
+                code: place(40, 24, 20),
+                url: place(4, 60, 8),
+                expiry: place(60, 74, 8),
+                saving: place(58, 24, 20),
+                warning: place(52, 60, 8),
+                error: place(2, 22, 8),
+                error_columns: 48,
+                error_rows: 7,
+            },
+            Panel::Large => Layout {
+                columns: 26,
+                rows: 9,
+                heading: place(10, 0, 16),
+                pulse: place(0, 0, 16),
+                email: place(96, 4, 10),
+                events: place(0, 20, 16),
+                instructions: place(24, 24, 16),
+                code: place(64, 48, 24),
+                url: place(20, 108, 12),
+                expiry: place(82, 122, 12),
+                saving: place(78, 48, 24),
+                warning: place(40, 108, 12),
+                error: place(4, 40, 12),
+                error_columns: 34,
+                error_rows: 9,
+            },
+        }
+    }
+
+    /// The columns and rows of a portrait profile are those of its
+    /// events font that fit below the heading on the panel on its side.
+    fn portrait_profile(panel: Panel) -> Layout {
+        let place = |x, y, size| Placement { x, y, size };
+        let (width, height) = Rotation::Quarter.unturned(panel.pixels());
+        let columns = |events: Placement| (width / cell_width(events.size)) as usize;
+        let rows = |events: Placement| {
+            ((height - events.y as u32) / line_height(events.size)) as usize
+        };
+        match panel {
+            Panel::Small => {
+                let events = place(0, 20, 10);
+                Layout {
+                    columns: columns(events),
+                    rows: rows(events),
+                    heading: place(4, 0, 10),
+                    pulse: place(0, 0, 10),
+                    email: place(0, 12, 6),
+                    events,
+                    instructions: place(4, 8, 8),
+                    code: place(12, 36, 14),
+                    url: place(0, 70, 6),
+                    expiry: place(20, 84, 8),
+                    saving: place(20, 36, 14),
+                    warning: place(12, 70, 6),
+                    error: place(2, 18, 6),
+                    error_columns: 30,
+                    error_rows: 13,
+                }
+            }
+            Panel::Medium => {
+                let events = place(0, 22, 10);
+                Layout {
+                    columns: columns(events),
+                    rows: rows(events),
+                    heading: place(6, 0, 10),
+                    pulse: place(0, 0, 10),
+                    email: place(0, 12, 6),
+                    events,
+                    instructions: place(4, 8, 8),
+                    code: place(16, 40, 18),
+                    url: place(2, 80, 6),
+                    expiry: place(30, 96, 8),
+                    saving: place(29, 40, 18),
+                    warning: place(28, 80, 6),
+                    error: place(2, 20, 6),
+                    error_columns: 40,
+                    error_rows: 15,
+                }
+            }
+            Panel::Wide => {
+                let events = place(0, 24, 10);
+                Layout {
+                    columns: columns(events),
+                    rows: rows(events),
+                    heading: place(4, 0, 12),
+                    pulse: place(0, 0, 12),
+                    email: place(0, 14, 6),
+                    events,
+                    instructions: place(4, 20, 8),
+                    code: place(12, 50, 14),
+                    url: place(0, 90, 6),
+                    expiry: place(20, 104, 8),
+                    saving: place(20, 50, 14),
+                    warning: place(12, 90, 6),
+                    error: place(2, 32, 6),
+                    error_columns: 30,
+                    error_rows: 21,
+                }
+            }
+            Panel::Large => {
+                let events = place(0, 32, 16);
+                Layout {
+                    columns: columns(events),
+                    rows: rows(events),
+                    heading: place(10, 0, 16),
+                    pulse: place(0, 0, 16),
+                    email: place(0, 18, 10),
+                    events,
+                    instructions: place(10, 40, 12),
+                    code: place(25, 80, 24),
+                    url: place(24, 140, 8),
+                    expiry: place(50, 160, 12),
+                    saving: place(39, 80, 24),
+                    warning: place(16, 140, 10),
+                    error: place(4, 60, 10),
+                    error_columns: 28,
+                    error_rows: 17,
+                }
+            }
+        }
+    }
+
+    fn dims(&self) -> Dims {
+        Dims(GlyphXCnt(self.columns), GlyphYCnt(self.rows))
+    }
+}
+
+/// The number of days listed in the week view, starting with the
+/// display date.
+pub const WEEK_DAYS: i64 = 7;
+
+/// How the events are listed.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum View {
+    /// The events of the display date, with the current time among them.
+    #[default]
+    Day,
+    /// A line for each event over a week, under the day it falls on.
+    Week,
+    /// A grid of the month with a marker for how busy each day is.
+    Month,
+    /// Only the event in progress and the next one, in large type.
+    UpNext,
+}
+
+impl View {
+    pub fn next(self) -> View {
+        match self {
+            View::Day => View::Week,
+            View::Week => View::Month,
+            View::Month => View::UpNext,
+            View::UpNext => View::Day,
+        }
+    }
+}
 
 #[derive(PartialEq)]
 pub enum RefreshType {
@@ -99,18 +355,32 @@ pub enum RefreshType {
 }
 
 pub struct Renderer {
-    pipe: RenderPipeline,
+    pipe: Box<dyn DisplayBackend>,
     state: Option<DisplayMachine>,
     status: Status,
     pulse_on: bool,
     formatter: LeftFormatter,
-    dims: Dims,
+    layout: Layout,
     events: Option<EventContent>,
+    week: Option<Vec<(DateTime<Local>, Option<AppsReadonly>)>>,
+    month: Option<Vec<Option<usize>>>,
+    up_next: bool,
+    events_size: u32,
+    /// Shown in place of the e-mail address when the events are next
+    /// drawn.
+    notice: Option<String>,
 }
 
 struct EventContent {
     date: DateTime<Local>,
     apps: AppsReadonly,
+    freshness: Freshness,
+}
+
+enum Freshness {
+    Current,
+    Saved(DateTime<Local>),
+    Unavailable,
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -150,9 +420,10 @@ enum DisplayRecord {
 }
 
 impl Renderer {
-    pub fn new() -> Result<Renderer, Error> {
+    pub fn new(pipe: Box<dyn DisplayBackend>) -> Result<Renderer, Error> {
+        let layout = Layout::profile(pipe.panel().unwrap_or_default(), Rotation::None);
         Ok(Renderer {
-            pipe: RenderPipeline::new()?,
+            pipe,
             state: Some(DisplayMachine::new(
                 (),
                 Box::new(|mach: DisplayAtEnd| {
@@ -163,34 +434,80 @@ impl Renderer {
                         DisplayAtEnd::SaveWarning(st) => DisplayTerminals::SaveWarning(st),
                         DisplayAtEnd::UserCode(st) => DisplayTerminals::UserCode(st),
                         DisplayAtEnd::Events(st) => DisplayTerminals::Events(st),
+                        DisplayAtEnd::Message(st) => DisplayTerminals::Message(st),
                         DisplayAtEnd::Unknown(st) => DisplayTerminals::Unknown(st),
                     }
                 }),
             )),
             status: Status::AllOk,
             pulse_on: false,
-            formatter: LeftFormatter::new(SCREEN_DIMS),
-            dims: SCREEN_DIMS,
+            formatter: LeftFormatter::new(layout.dims()),
+            layout,
             events: None,
+            week: None,
+            month: None,
+            up_next: false,
+            events_size: 0,
+            notice: None,
         })
     }
 
-    pub fn wait_for_server() -> Result<Renderer, Error> {
-        RenderPipeline::wait_for_server()?;
-        Renderer::new()
+    /// Lays out the events screen differently from the next time it's
+    /// drawn in full.
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.formatter = LeftFormatter::new(layout.dims());
+        self.layout = layout;
     }
 
-    pub fn disconnect_quits_server(&mut self) -> Result<(), Error> {
-        let mut ops: Vec<Op> = Vec::with_capacity(1);
-        ops.push(Op::QuitWhenDone);
-        self.pipe.send(ops.iter(), false)?;
+    /// The panel being drawn on, if the display knows it.
+    pub fn panel(&self) -> Option<Panel> {
+        self.pipe.panel()
+    }
 
-        Ok(())
+    /// Turns the screen from the next time it's drawn in full. The
+    /// layout should be changed to suit.
+    pub fn set_rotation(&mut self, rotation: Rotation) {
+        self.pipe.rotate(rotation);
+    }
+
+    /// Lists the days following the display date below its events, a
+    /// line an event, or only the display date's events when there are
+    /// none. A day that hasn't been read has no events. Takes effect
+    /// the next time the events are drawn.
+    pub fn set_week(&mut self, following: Option<Vec<(DateTime<Local>, Option<AppsReadonly>)>>) {
+        self.week = following;
+    }
+
+    /// Shows the month of the display date as a grid in place of its
+    /// events, given the number of events on each day of the month, or
+    /// None for a day that hasn't been read. Takes effect the next time
+    /// the events are drawn.
+    pub fn set_month(&mut self, busy: Option<Vec<Option<usize>>>) {
+        self.month = busy;
+    }
+
+    /// Shows only the event in progress and the next one in place of
+    /// the list of events, from the next time they're drawn.
+    pub fn set_up_next(&mut self, on: bool) {
+        self.up_next = on;
+    }
+
+    /// Moves the selection of the month grid to date, which is in the
+    /// month shown.
+    pub fn select_date(&mut self, date: DateTime<Local>, now: Now) -> Result<(), Error> {
+        if let Some(ref mut content) = self.events {
+            content.date = date;
+        }
+        self.render_events(RefreshType::Partial, now, |_, _| GlyphYCnt(0))
     }
 
     fn format<E: DisplayableOccasion + Debug>(event: &E, now: &Now) -> (Option<Ordering>, String) {
         let mut event_str = String::with_capacity(40);
 
+        if let Some(tag) = event.tag() {
+            event_str.push(tag);
+            event_str.push(' ');
+        }
         event_str.push_str(&event.period());
 
         let ordering = event.partial_chron_cmp(now);
@@ -217,6 +534,7 @@ impl Renderer {
                 SaveWarning(st) => Unknown(st.into()),
                 UserCode(st) => Unknown(st.into()),
                 Events(st) => Unknown(st.into()),
+                Message(st) => Unknown(st.into()),
                 Unknown(st) => Unknown(st),
             },
         );
@@ -235,6 +553,7 @@ impl Renderer {
                 SaveWarning(st) => Empty(st.into()),
                 UserCode(st) => Empty(st.into()),
                 Events(st) => Empty(st.into()),
+                Message(st) => Empty(st.into()),
                 Unknown(st) => Empty(st.into()),
             },
         );
@@ -286,14 +605,14 @@ impl Renderer {
         ops.push(Op::Clear);
         ops.push(Op::AddText(
             "SAVING!".to_string(),
-            Pos(78, 48),
-            LARGE_SIZE,
+            self.layout.saving.pos(),
+            self.layout.saving.size,
             "Code".to_string(),
         ));
         ops.push(Op::AddText(
             "Do not disconnect power.".to_string(),
-            Pos(40, 108),
-            SMALL_SIZE,
+            self.layout.warning.pos(),
+            self.layout.warning.size,
             "Instr2".to_string(),
         ));
         ops.push(Op::WriteAll(PartialUpdate(false)));
@@ -310,6 +629,7 @@ impl Renderer {
                 SaveWarning(st) => SaveWarning(st),
                 UserCode(st) => SaveWarning(st.into()),
                 Events(st) => SaveWarning(st.into()),
+                Message(st) => SaveWarning(st.into()),
                 Unknown(st) => SaveWarning(st.into()),
             },
         );
@@ -329,26 +649,26 @@ impl Renderer {
         ops.push(Op::Clear);
         ops.push(Op::AddText(
             "Please enter the code:".to_string(),
-            INSTR1_POS,
-            INSTR_SIZE,
+            self.layout.instructions.pos(),
+            self.layout.instructions.size,
             "Instr1".to_string(),
         ));
         ops.push(Op::AddText(
             user_code.to_string(),
-            CODE_POS,
-            LARGE_SIZE,
+            self.layout.code.pos(),
+            self.layout.code.size,
             "Code".to_string(),
         ));
         ops.push(Op::AddText(
             format!("at {}", url),
-            INSTR2_POS,
-            SMALL_SIZE,
+            self.layout.url.pos(),
+            self.layout.url.size,
             "Instr2".to_string(),
         ));
         ops.push(Op::AddText(
             format!("before {}", expires_at.format(TIME_FORMAT).to_string()),
-            EXPIRY_POS,
-            SMALL_SIZE,
+            self.layout.expiry.pos(),
+            self.layout.expiry.size,
             "Expiry".to_string(),
         ));
         ops.push(Op::WriteAll(PartialUpdate(false)));
@@ -365,6 +685,7 @@ impl Renderer {
                 SaveWarning(st) => UserCode(st.into()),
                 UserCode(st) => UserCode(st),
                 Events(st) => UserCode(st.into()),
+                Message(st) => UserCode(st.into()),
                 Unknown(st) => UserCode(st.into()),
             },
         );
@@ -372,6 +693,62 @@ impl Renderer {
         Ok(())
     }
 
+    /// Shows a problem that needs attention, with the message broken
+    /// into as many lines as fit below the heading.
+    pub fn display_error(&mut self, message: &str) -> Result<(), Error> {
+        self.unset_state();
+        self.events = None;
+        let mut lines: Vec<String> = Vec::new();
+        for word in message.split_whitespace() {
+            match lines.last_mut() {
+                Some(line) if line.len() + 1 + word.len() <= self.layout.error_columns => {
+                    line.push(' ');
+                    line.push_str(word);
+                }
+                _ => lines.push(word.to_string()),
+            }
+        }
+
+        let error = self.layout.error;
+        let line_height = error.size as i32 + ERROR_LINE_SPACING;
+        let mut ops: Vec<Op> = Vec::with_capacity(self.layout.error_rows + 3);
+        ops.push(Op::Clear);
+        ops.push(Op::AddText(
+            ERROR_HEADING.to_string(),
+            self.layout.instructions.pos(),
+            self.layout.instructions.size,
+            "Heading".to_string(),
+        ));
+        for (i, line) in lines.into_iter().take(self.layout.error_rows).enumerate() {
+            ops.push(Op::AddText(
+                line,
+                Pos(error.x, error.y + line_height * i as i32),
+                error.size,
+                format!("Error{}", i),
+            ));
+        }
+        ops.push(Op::WriteAll(PartialUpdate(false)));
+
+        self.pipe.send(ops.iter(), false)?;
+
+        self.state = Some(
+            match self
+                .state
+                .take()
+                .expect("no state in Renderer.display_error()")
+            {
+                Empty(st) => Message(st.into()),
+                SaveWarning(st) => Message(st.into()),
+                UserCode(st) => Message(st.into()),
+                Events(st) => Message(st.into()),
+                Message(st) => Message(st),
+                Unknown(st) => Message(st.into()),
+            },
+        );
+
+        Ok(())
+    }
+
     pub fn refresh_date(&mut self, date: &DateTime<Local>) -> Result<(), Error> {
         if !self.events_displayed() {
             return Ok(());
@@ -390,6 +767,38 @@ impl Renderer {
         Ok(())
     }
 
+    /// Draws the events on screen again in full, after the layout has
+    /// changed say. Nothing happens unless events are on screen.
+    pub fn redraw(
+        &mut self,
+        now: Now,
+        pos_calculator: impl FnMut(GlyphYCnt, GlyphYCnt) -> GlyphYCnt,
+    ) -> Result<(), Error> {
+        if !self.events_displayed() {
+            return Ok(());
+        }
+        self.render_events(RefreshType::Full, now, pos_calculator)
+    }
+
+    /// Shows message where the e-mail address usually goes, until the
+    /// events are next drawn. When they aren't on screen it's shown once
+    /// they are instead.
+    pub fn display_notice(&mut self, message: &str) -> Result<(), Error> {
+        let notice: String = message.chars().take(self.layout.error_columns).collect();
+        if !self.events_displayed() {
+            self.notice = Some(notice);
+            return Ok(());
+        }
+
+        let ops = [
+            Op::UpdateText(EMAIL_ID.to_string(), notice),
+            Op::WriteAll(PartialUpdate(true)),
+        ];
+
+        self.pipe.send(ops.iter(), false)?;
+        Ok(())
+    }
+
     pub fn scroll_events(
         &mut self,
         now: Now,
@@ -398,6 +807,110 @@ impl Renderer {
         self.render_events(RefreshType::Partial, now, pos_calculator)
     }
 
+    fn week_lines(
+        &self,
+        content: &EventContent,
+        following: &[(DateTime<Local>, Option<AppsReadonly>)],
+        now: &Now,
+    ) -> Result<Vec<String>, Error> {
+        let first = (content.date, Some(content.apps.clone()));
+        let mut lines = Vec::new();
+        for (date, apps) in once(&first).chain(following.iter()) {
+            lines.push(date.format(WEEK_DAY_FORMAT).to_string());
+            match apps {
+                None => lines.push(NOT_READ.to_string()),
+                Some(apps) if apps.events().is_empty() => lines.push(NO_EVENTS.to_string()),
+                Some(apps) => {
+                    let mut events = apps.events();
+                    events.sort();
+                    for event in events.iter() {
+                        //only the first row is kept to fit the week on screen
+                        let rows = self.formatter.just_lines(&Renderer::format(event, now).1)?;
+                        lines.extend(rows.into_iter().take(1));
+                    }
+                }
+            }
+        }
+        Ok(lines)
+    }
+
+    fn month_lines(&self, selected: &DateTime<Local>, busy: &[Option<usize>]) -> Vec<String> {
+        //day numbers are left out when a week of them doesn't fit
+        let cell = if self.layout.columns >= 3 * WEEKDAY_INITIALS.len() {
+            3
+        } else {
+            2
+        };
+        let first = *selected - chrono::Duration::days(i64::from(selected.day0()));
+        let blank = " ".repeat(cell);
+        let mut cells = vec![blank; first.weekday().num_days_from_monday() as usize];
+        for (index, count) in busy.iter().enumerate() {
+            let marker = match count {
+                _ if index == selected.day0() as usize => SELECTED_MARKER,
+                None => UNREAD_MARKER,
+                Some(count) => BUSY_MARKERS[(*count).min(BUSY_MARKERS.len() - 1)],
+            };
+            cells.push(if cell == 3 {
+                format!("{:>2}{}", index + 1, marker)
+            } else {
+                format!("{} ", marker)
+            });
+        }
+
+        let mut lines: Vec<String> = cells
+            .chunks(WEEKDAY_INITIALS.len())
+            .map(|week| week.concat().trim_end().to_string())
+            .collect();
+        if lines.len() < self.layout.rows {
+            let initials = WEEKDAY_INITIALS
+                .iter()
+                .map(|initials| format!("{:<1$}", &initials[..cell - 1], cell))
+                .collect::<String>();
+            lines.insert(0, initials.trim_end().to_string());
+        }
+        lines
+    }
+
+    fn month_summary(selected: &DateTime<Local>, busy: &[Option<usize>]) -> String {
+        let events = match busy.get(selected.day0() as usize) {
+            Some(Some(0)) => NO_EVENTS.to_string(),
+            Some(Some(1)) => "1 event".to_string(),
+            Some(Some(count)) => format!("{} events", count),
+            _ => NOT_READ.to_string(),
+        };
+        format!("{}. {}", selected.format(MONTH_FORMAT), events)
+    }
+
+    fn up_next_lines(content: &EventContent, now: &Now) -> Vec<String> {
+        let mut events = content.apps.events();
+        events.sort();
+        let current = events
+            .iter()
+            .find(|ev| ev.partial_chron_cmp(now) == Some(Ordering::Equal));
+        let next = events
+            .iter()
+            .find(|ev| ev.partial_chron_cmp(now) == Some(Ordering::Greater));
+
+        let current = match current {
+            Some(ev) => format!("{}{}", NOW_PREFIX, ev.description()),
+            None => FREE_NOW.to_string(),
+        };
+        let next = match next {
+            Some(ev) if content.date.date() == now.as_ref().date() => {
+                //rounded up so that an event about to start isn't 0 min away
+                let secs = (*ev.start.as_ref() - *now.as_ref()).num_seconds();
+                format!("{} in {} min", ev.description(), (secs + 59) / 60)
+            }
+            Some(ev) => format!(
+                "{} at {}",
+                ev.description(),
+                ev.start.as_ref().format(TIME_FORMAT)
+            ),
+            None => NOTHING_NEXT.to_string(),
+        };
+        vec![current, next]
+    }
+
     fn date_start(date: &DateTime<Local>) -> Result<DateTime<Local>, Error> {
         date.with_hour(0)
             .and_then(|date| date.with_minute(0))
@@ -412,11 +925,21 @@ impl Renderer {
         now: Now,
         mut pos_calculator: impl FnMut(GlyphYCnt, GlyphYCnt) -> GlyphYCnt,
     ) -> Result<(), Error> {
-        if render_type == RefreshType::Partial {
-            if !self.events_displayed() {
-                return Ok(());
-            }
+        if render_type == RefreshType::Partial && !self.events_displayed() {
+            return Ok(());
+        }
+        //the events are drawn in full when their type size changes
+        let events_size = if self.up_next {
+            self.layout.code.size
+        } else {
+            self.layout.events.size
+        };
+        let render_type = if events_size != self.events_size {
+            RefreshType::Full
         } else {
+            render_type
+        };
+        if render_type == RefreshType::Full {
             self.unset_state();
         }
 
@@ -424,14 +947,64 @@ impl Renderer {
             let display_date = Renderer::date_start(&content.date)?;
             let today = Renderer::date_start(&now.as_ref())?;
             let mut ops: Vec<Op> = Vec::with_capacity(6);
-            let _events_queued = if display_date != today && content.apps.events().len() == 0 {
+            let _events_queued = if self.up_next {
+                //the user code's type size, with the events' lines and columns scaled to suit
+                let scale =
+                    |count: usize| count * self.layout.events.size as usize / events_size as usize;
+                let formatter = LeftFormatter::new(Dims(
+                    GlyphXCnt(scale(self.layout.columns)),
+                    GlyphYCnt(scale(self.layout.rows)),
+                ));
+                let lines = Renderer::up_next_lines(content, &now).join("\n");
+                let up_next = formatter.just(&lines)?;
+                if render_type == RefreshType::Full {
+                    ops.push(Op::Clear);
+                    ops.push(Op::AddText(
+                        up_next,
+                        self.layout.events.pos(),
+                        events_size,
+                        EVENTS_ID.to_string(),
+                    ));
+                } else {
+                    ops.push(Op::UpdateText(EVENTS_ID.to_string(), up_next));
+                }
+            } else if let Some(ref busy) = self.month {
+                let grid = self.month_lines(&content.date, busy).join("\n");
+                if render_type == RefreshType::Full {
+                    ops.push(Op::Clear);
+                    ops.push(Op::AddText(
+                        grid,
+                        self.layout.events.pos(),
+                        self.layout.events.size,
+                        EVENTS_ID.to_string(),
+                    ));
+                } else {
+                    ops.push(Op::UpdateText(EVENTS_ID.to_string(), grid));
+                }
+            } else if let Some(ref following) = self.week {
+                let lines = self.week_lines(content, following, &now)?;
+                let pos = pos_calculator(GlyphYCnt(lines.len()), self.layout.dims().1);
+                let justified_events = lines[pos.0..].join("\n");
+
+                if render_type == RefreshType::Full {
+                    ops.push(Op::Clear);
+                    ops.push(Op::AddText(
+                        justified_events,
+                        self.layout.events.pos(),
+                        self.layout.events.size,
+                        EVENTS_ID.to_string(),
+                    ));
+                } else {
+                    ops.push(Op::UpdateText(EVENTS_ID.to_string(), justified_events));
+                }
+            } else if display_date != today && content.apps.events().len() == 0 {
                 let displayable_events = NO_EVENTS.to_string();
                 if render_type == RefreshType::Full {
                     ops.push(Op::Clear);
                     ops.push(Op::AddText(
                         displayable_events,
-                        EVENTS_POS,
-                        EVENTS_SIZE,
+                        self.layout.events.pos(),
+                        self.layout.events.size,
                         EVENTS_ID.to_string(),
                     ));
                 } else {
@@ -596,7 +1169,7 @@ impl Renderer {
                         .join("\n")
                 };
                 let lines = joined.lines().collect::<Vec<&str>>();
-                let pos = pos_calculator(GlyphYCnt(lines.len()), self.dims.1);
+                let pos = pos_calculator(GlyphYCnt(lines.len()), self.layout.dims().1);
                 let justified_events = lines[pos.0..].join("\n");
 
                 if render_type == RefreshType::Full {
@@ -604,8 +1177,8 @@ impl Renderer {
 
                     ops.push(Op::AddText(
                         justified_events,
-                        EVENTS_POS,
-                        EVENTS_SIZE,
+                        self.layout.events.pos(),
+                        self.layout.events.size,
                         EVENTS_ID.to_string(),
                     ));
                 } else {
@@ -614,30 +1187,35 @@ impl Renderer {
             };
 
             let heading = content.date.format(DATE_FORMAT).to_string();
-            let displayable_email = if let Some(Email(email_address)) = content.apps.email() {
-                email_address
-            } else {
-                NO_EMAIL.to_string()
+            let displayable_email = match (&self.month, &content.freshness, content.apps.email()) {
+                _ if self.notice.is_some() => self.notice.take().unwrap_or_default(),
+                (Some(busy), _, _) => Renderer::month_summary(&content.date, busy),
+                (None, Freshness::Current, Some(Email(email_address))) => email_address,
+                (None, Freshness::Current, None) => NO_EMAIL.to_string(),
+                (None, Freshness::Saved(downloaded), _) => {
+                    downloaded.format(SAVED_FORMAT).to_string()
+                }
+                (None, Freshness::Unavailable, _) => NOT_SAVED.to_string(),
             };
             if render_type == RefreshType::Full {
                 ops.push(Op::AddText(
                     heading,
-                    HEADING_POS,
-                    HEADING_SIZE,
+                    self.layout.heading.pos(),
+                    self.layout.heading.size,
                     HEADING_ID.to_string(),
                 ));
 
                 ops.push(Op::AddText(
                     STATUS_FLASH_OFF.to_string(),
-                    PULSE_POS,
-                    PULSE_SIZE,
+                    self.layout.pulse.pos(),
+                    self.layout.pulse.size,
                     PULSE_ID.to_string(),
                 ));
 
                 ops.push(Op::AddText(
                     displayable_email,
-                    EMAIL_POS,
-                    EMAIL_SIZE,
+                    self.layout.email.pos(),
+                    self.layout.email.size,
                     EMAIL_ID.to_string(),
                 ));
 
@@ -653,6 +1231,7 @@ impl Renderer {
             }
 
             self.pipe.send(ops.iter(), false)?;
+            self.events_size = events_size;
             self.state = Some(
                 match self
                     .state
@@ -663,6 +1242,7 @@ impl Renderer {
                     SaveWarning(st) => Events(st.into()),
                     UserCode(st) => Events(st.into()),
                     Events(st) => Events(st),
+                    Message(st) => Events(st.into()),
                     Unknown(st) => Events(st.into()),
                 },
             );
@@ -681,8 +1261,264 @@ impl Renderer {
         now: Now,
         pos_calculator: impl FnMut(GlyphYCnt, GlyphYCnt) -> GlyphYCnt,
     ) -> Result<(), Error> {
-        let content = EventContent { date, apps };
+        let content = EventContent {
+            date,
+            apps,
+            freshness: Freshness::Current,
+        };
         self.events = Some(content);
         self.render_events(render_type, now, pos_calculator)
     }
+
+    /// Shows events saved when the network was last available in place
+    /// of the e-mail address, along with when they were downloaded.
+    pub fn display_saved_events(
+        &mut self,
+        date: DateTime<Local>,
+        saved: Option<(AppsReadonly, DateTime<Local>)>,
+        now: Now,
+        pos_calculator: impl FnMut(GlyphYCnt, GlyphYCnt) -> GlyphYCnt,
+    ) -> Result<(), Error> {
+        let render_type = if self.events_displayed() {
+            RefreshType::Partial
+        } else {
+            RefreshType::Full
+        };
+        let content = match saved {
+            Some((apps, downloaded)) => EventContent {
+                date,
+                apps,
+                freshness: Freshness::Saved(downloaded),
+            },
+            None => EventContent {
+                date,
+                apps: AppsReadonly::saved(None, Vec::new()),
+                freshness: Freshness::Unavailable,
+            },
+        };
+        self.events = Some(content);
+        self.render_events(render_type, now, pos_calculator)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::{
+        cal_display::{Layout, RefreshType, Renderer},
+        cal_machine::evs::{AppsReadonly, EndDate, Event, Now, StartDate},
+        display::{
+            panel::{Panel, Rotation},
+            DisplayBackend, Error, Operation,
+        },
+        formatter::GlyphYCnt,
+    };
+    use chrono::prelude::*;
+    use std::{
+        slice::Iter,
+        sync::{Arc, Mutex},
+    };
+
+    pub type Log = Arc<Mutex<Vec<String>>>;
+
+    /// Keeps every piece of text sent to the display.
+    pub struct Recorder(pub Log);
+
+    impl Recorder {
+        /// A renderer drawing to a new recorder, with the texts it keeps.
+        pub fn renderer() -> (Log, Renderer) {
+            let texts: Log = Arc::new(Mutex::new(Vec::new()));
+            let renderer = Renderer::new(Box::new(Recorder(texts.clone()))).expect("renderer");
+            (texts, renderer)
+        }
+    }
+
+    impl DisplayBackend for Recorder {
+        fn send(&mut self, els: Iter<Operation>, _sync: bool) -> Result<(), Error> {
+            let mut texts = self.0.lock().expect("recorded texts");
+            for el in els {
+                match el {
+                    Operation::AddText(text, _, _, _) | Operation::UpdateText(_, text) => {
+                        texts.push(text.clone())
+                    }
+                    _ => (),
+                }
+            }
+            Ok(())
+        }
+    }
+
+    pub fn shown(texts: &[String], text: &str) -> bool {
+        texts.iter().any(|shown| shown.contains(text))
+    }
+
+    #[test]
+    fn week_view_lists_days_under_headings() {
+        let (texts, mut renderer) = Recorder::renderer();
+        let today = Local::today().and_hms(0, 0, 0);
+        let tomorrow = today + chrono::Duration::days(1);
+        let dentist = Event {
+            summary: "Dentist".to_string(),
+            description: None,
+            all_consuming: true,
+            start: StartDate(tomorrow + chrono::Duration::hours(9)),
+            end: EndDate(tomorrow + chrono::Duration::hours(10)),
+            tag: None,
+        };
+        renderer.set_week(Some(vec![
+            (tomorrow, Some(AppsReadonly::saved(None, vec![dentist]))),
+            (tomorrow + chrono::Duration::days(1), None),
+        ]));
+        renderer
+            .display_events(
+                today,
+                AppsReadonly::saved(None, Vec::new()),
+                RefreshType::Full,
+                Now(Local::now()),
+                |_, _| GlyphYCnt(0),
+            )
+            .expect("week displayed");
+
+        let events = texts.lock().expect("recorded texts")[0].clone();
+        let lines: Vec<&str> = events.lines().collect();
+        assert_eq!(lines[0], today.format("%a %e %b").to_string());
+        assert_eq!(lines[1], "No events");
+        assert_eq!(lines[2], tomorrow.format("%a %e %b").to_string());
+        assert_eq!(lines[3], "09:00-10:00 Dentist");
+        assert_eq!(lines[5], "Not read yet");
+    }
+
+    #[test]
+    fn month_view_marks_busy_days() {
+        let (texts, mut renderer) = Recorder::renderer();
+        //1 Oct 2026 is a Thursday
+        let selected = Local.ymd(2026, 10, 2).and_hms(0, 0, 0);
+        let mut busy = vec![Some(0); 31];
+        busy[0] = Some(1);
+        busy[2] = None;
+        busy[3] = Some(9);
+        renderer.set_month(Some(busy));
+        renderer
+            .display_events(
+                selected,
+                AppsReadonly::saved(None, Vec::new()),
+                RefreshType::Full,
+                Now(Local::now()),
+                |_, _| GlyphYCnt(0),
+            )
+            .expect("month displayed");
+
+        let recorded = texts.lock().expect("recorded texts").clone();
+        let lines: Vec<&str> = recorded[0].lines().collect();
+        assert_eq!(lines[0], "Mo Tu We Th Fr Sa Su");
+        assert_eq!(lines[1], "          1. 2< 3? 4#");
+        assert_eq!(lines[2], " 5  6  7  8  9 10 11");
+        assert!(shown(&recorded, "Oct 2026. No events"));
+    }
+
+    #[test]
+    fn up_next_counts_down_to_the_next_event() {
+        let (texts, mut renderer) = Recorder::renderer();
+        let now = Local.ymd(2026, 10, 17).and_hms(9, 48, 0);
+        let meeting = |summary: &str, start: DateTime<Local>, mins| Event {
+            summary: summary.to_string(),
+            description: None,
+            all_consuming: true,
+            start: StartDate(start),
+            end: EndDate(start + chrono::Duration::minutes(mins)),
+            tag: None,
+        };
+        let apps = AppsReadonly::saved(
+            None,
+            vec![
+                meeting("Standup", now + chrono::Duration::minutes(12), 15),
+                meeting("Review", now - chrono::Duration::minutes(48), 60),
+            ],
+        );
+        renderer.set_up_next(true);
+        renderer
+            .display_events(
+                now.date().and_hms(0, 0, 0),
+                apps,
+                RefreshType::Full,
+                Now(now),
+                |_, _| GlyphYCnt(0),
+            )
+            .expect("up next displayed");
+        renderer
+            .scroll_events(Now(now + chrono::Duration::minutes(20)), |_, _| GlyphYCnt(0))
+            .expect("time updated");
+
+        let recorded = texts.lock().expect("recorded texts").clone();
+        assert!(shown(&recorded, "Now: Review"));
+        assert!(shown(&recorded, "Standup in 12 min"));
+        assert!(shown(&recorded, "Now: Standup"));
+        assert!(shown(&recorded, "Nothing next"));
+    }
+
+    #[test]
+    fn up_next_gives_start_times_on_other_days() {
+        let (texts, mut renderer) = Recorder::renderer();
+        let now = Local.ymd(2026, 10, 17).and_hms(9, 48, 0);
+        let tomorrow = Local.ymd(2026, 10, 18).and_hms(0, 0, 0);
+        let standup = Event {
+            summary: "Standup".to_string(),
+            description: None,
+            all_consuming: true,
+            start: StartDate(tomorrow + chrono::Duration::hours(10)),
+            end: EndDate(tomorrow + chrono::Duration::minutes(615)),
+            tag: None,
+        };
+        renderer.set_up_next(true);
+        renderer
+            .display_events(
+                tomorrow,
+                AppsReadonly::saved(None, vec![standup]),
+                RefreshType::Full,
+                Now(now),
+                |_, _| GlyphYCnt(0),
+            )
+            .expect("up next displayed");
+
+        let recorded = texts.lock().expect("recorded texts").clone();
+        assert!(shown(&recorded, "Standup at 10:00"));
+        assert!(!shown(&recorded, " min"));
+    }
+
+    #[test]
+    fn notice_waits_for_the_events() {
+        let (texts, mut renderer) = Recorder::renderer();
+        renderer.display_notice("Settings not reloaded").expect("notice");
+        assert!(!shown(&texts.lock().expect("recorded texts"), "not reloaded"));
+
+        let today = Local::today().and_hms(0, 0, 0);
+        renderer
+            .display_events(
+                today,
+                AppsReadonly::saved(None, Vec::new()),
+                RefreshType::Full,
+                Now(Local::now()),
+                |_, _| GlyphYCnt(0),
+            )
+            .expect("events displayed");
+        renderer
+            .scroll_events(Now(Local::now()), |_, _| GlyphYCnt(0))
+            .expect("time updated");
+
+        let recorded = texts.lock().expect("recorded texts").clone();
+        let notices = recorded.iter().filter(|text| text.contains("not reloaded")).count();
+        assert_eq!(notices, 1);
+    }
+
+    #[test]
+    fn portrait_profiles_are_narrower() {
+        for panel in [Panel::Small, Panel::Medium, Panel::Wide, Panel::Large].iter() {
+            let landscape = Layout::profile(*panel, Rotation::None);
+            let portrait = Layout::profile(*panel, Rotation::Quarter);
+            assert!(portrait.columns < landscape.columns, "{:?}", panel);
+            assert!(portrait.rows > landscape.rows, "{:?}", panel);
+            assert_eq!(portrait, Layout::profile(*panel, Rotation::ThreeQuarters));
+        }
+        assert_eq!(Layout::profile(Panel::Small, Rotation::Quarter).columns, 16);
+        assert_eq!(Layout::profile(Panel::Large, Rotation::Quarter).columns, 19);
+    }
 }
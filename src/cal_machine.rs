@@ -14,14 +14,30 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+pub mod cache;
+mod caldav;
 pub mod evs;
+mod ical;
+mod ics;
+#[cfg(test)]
+mod mock_google;
 mod retriever;
+pub mod sources;
+mod synced;
+pub mod terminal;
+mod webcal;
+mod worker;
 
 use crate::{
-    cal_display::{self, Error as CalDisplayError, RefreshType, Renderer, Status},
+    cal_display::{self, Error as CalDisplayError, RefreshType, Renderer, Status, View, WEEK_DAYS},
     cal_machine::{
-        evs::{Appointments, Error as EvError, Now},
+        cache::EventCache,
+        evs::{Appointments, AppsReadonly, Error as EvError, Event, Now},
         instant_types::*,
+        sources::Calendars,
+        synced::SyncedCalendars,
+        terminal::CommandError,
+        worker::{CalendarEvents, CodeOutcome, Job, PageOutcome, PollOutcome, Reply, Worker},
     },
     cloneable,
     display::{self},
@@ -29,12 +45,13 @@ use crate::{
     formatter::{self, GlyphYCnt},
     gpio_in::{
         self, Button, DetectableDuration, Error as GPIO_Error, LongButtonEvent, LongPressButton,
-        LongReleaseDuration, Pin, GPIO, SW1_GPIO, SW2_GPIO, SW3_GPIO, SW4_GPIO,
+        LongReleaseDuration, Pin, Pins,
     },
+    settings::{Settings, Timing, SETTINGS_FILE},
     stm,
 };
 use chrono::{format::ParseError, prelude::*};
-use log::{trace};
+use log::{error, trace};
 use nix::{unistd::*, Error as NixError};
 use retriever::*;
 use serde::{Deserialize, Serialize};
@@ -43,10 +60,8 @@ use std::{
     fs::File,
     io::{self, BufRead, BufReader, BufWriter, Write},
     path::Path,
-    sync::{
-        atomic::{AtomicBool, Ordering as AtomicOrdering},
-        Arc,
-    },
+    sync::atomic::{AtomicBool, Ordering as AtomicOrdering},
+    collections::BTreeMap,
     thread,
     time::Duration,
 };
@@ -54,15 +69,22 @@ use std::{
 //trace_macros!(true);
 stm!(machine cal_stm, Machine, CalsAtEnd, CalTerminals, [ErrorWait] => LoadAuth() |end|, {
     [DisplayError] => ErrorWait(DownloadedAt) |end|;
-    [ErrorWait, LoadAuth, NetworkOutage, PollEvents, RefreshAuth] => RequestCodes() |end|;
+    [AwaitRefresh, ErrorWait, LoadAuth, NetworkOutage, PollEvents] => RequestCodes() |end|;
+    [RequestCodes] => AwaitCodes() |end|;
     [LoadAuth, NetworkOutage, PageEvents, PollEvents] => RefreshAuth(RefreshToken, PendingDisplayDate) |end|;
-    [DeviceAuthPoll, RefreshAuth, PollEvents] => ReadFirstEvents(Authenticators, RefreshedAt, RefreshType, PendingDisplayDate) |end|;
-    [RequestCodes] => DeviceAuthPoll(String, PeriodSeconds) |end|;
-    [LoadAuth, PageEvents, DeviceAuthPoll, ReadFirstEvents, RefreshAuth, RequestCodes] => DisplayError(String) |end|;
-    [ReadFirstEvents] => PageEvents(Authenticators, Option<PageToken>, Appointments, RefreshedAt, DownloadedAt, RefreshType, PendingDisplayDate) |end|;
+    [RefreshAuth] => AwaitRefresh(RefreshToken, PendingDisplayDate) |end|;
+    [AwaitEvents, AwaitPoll, AwaitRefresh, PollEvents] => ReadFirstEvents(Authenticators, RefreshedAt, RefreshType, PendingDisplayDate) |end|;
+    [AwaitCodes, AwaitPoll] => DeviceAuthPoll(String, PeriodSeconds, PolledAt) |end|;
+    [DeviceAuthPoll] => AwaitPoll(String, PeriodSeconds) |end|;
+    [AwaitCodes, AwaitEvents, AwaitPoll, AwaitRefresh, LoadAuth] => DisplayError(String) |end|;
+    [ReadFirstEvents, PageEvents] => AwaitEvents(Authenticators, Vec<CalendarPage>, RefreshedAt, DownloadedAt, RefreshType, PendingDisplayDate, TimeUpdatedAt) |end|;
+    [AwaitEvents] => PageEvents(Authenticators, Vec<CalendarPage>, RefreshedAt, DownloadedAt, RefreshType, PendingDisplayDate) |end|;
     [PageEvents] => PollEvents(Authenticators, RefreshedAt, DownloadedAt, TimeUpdatedAt, PendingDisplayDate) |end|;
-    [RefreshAuth, ReadFirstEvents, PageEvents] => CachedDisplay(RefreshToken, LastNetErrorAt) |end|;
-    [CachedDisplay] => NetworkOutage(RefreshToken, LastNetErrorAt, TimeUpdatedAt) |end|
+    [AwaitEvents, AwaitRefresh] => CachedDisplay(RefreshToken, LastNetErrorAt) |end|;
+    [CachedDisplay] => NetworkOutage(RefreshToken, LastNetErrorAt, TimeUpdatedAt) |end|;
+    [AwaitLocalEvents, LoadAuth, LocalEvents] => ReadLocalEvents(RefreshType, PendingDisplayDate) |end|;
+    [ReadLocalEvents] => AwaitLocalEvents(RefreshType, PendingDisplayDate, TimeUpdatedAt) |end|;
+    [AwaitLocalEvents] => LocalEvents(DownloadedAt, TimeUpdatedAt, PendingDisplayDate) |end|
 });
 //trace_macros!(false);
 
@@ -70,10 +92,10 @@ type PeriodSeconds = u64;
 type AuthTokens = (RefreshToken, RefreshResponse);
 
 const PREEMPTIVE_REFRESH_OFFSET_MINS: Duration = Duration::from_secs(240);
-const RECHECK_PERIOD: Duration = Duration::from_secs(300);
-const TIME_UPDATE_PERIOD: Duration = Duration::from_secs(60);
-const BUTTON_POLL_PERIOD: Duration = Duration::from_millis(25);
-const V_POS_INC: usize = 5;
+pub const REFRESH_TOKEN_FILE: &str = "refresh.json";
+const HTTP_ERROR: &str = "HTTP error";
+const ACCESS_DENIED: &str = "User has refused to grant access to this calendar";
+const UNRECOGNISED_TOKEN_TYPE: &str = "Unrecognised token type";
 
 err!(Error {
     Chrono(ParseError),
@@ -83,7 +105,9 @@ err!(Error {
     Ev(EvError),
     Reqwest(reqwest::Error),
     GPIO(GPIO_Error),
-    Nix(NixError)
+    Nix(NixError),
+    Url(url::ParseError),
+    Command(CommandError)
 });
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -120,6 +144,7 @@ mod instant_types {
     instant!(DownloadedAt);
     instant!(TimeUpdatedAt);
     instant!(LastNetErrorAt);
+    instant!(PolledAt);
 }
 
 //pub struct PendingDisplayDate(DateTime<Local>);
@@ -151,12 +176,24 @@ impl RefreshToken {
     }
 }
 
+/// Something that has to be saved to the configuration partition.
+pub enum Persisted<'a> {
+    RefreshToken(&'a RefreshToken),
+    Events(&'a EventCache),
+}
+
 #[derive(Debug)]
 pub struct Authenticators {
     refresh_token: RefreshToken,
     volatiles: VolatileAuthenticator,
 }
 
+impl Authenticators {
+    fn bearer(&self) -> String {
+        format!("Bearer {}", self.volatiles.access_token)
+    }
+}
+
 impl From<PollResponse> for Authenticators {
     fn from(resp: PollResponse) -> Authenticators {
         Authenticators {
@@ -188,6 +225,219 @@ fn opt_filter<T>(val: &Option<T>, pred: impl Fn(&T) -> bool) -> bool {
     }
 }
 
+/// Keeps a day of events that the worker has read ahead, together with
+/// those of the other calendars. A failure is only logged as the day
+/// will be read again when it's displayed.
+fn store_prefetched(
+    date: DateTime<Local>,
+    day: reqwest::Result<Result<Vec<CalendarEvents>, reqwest::StatusCode>>,
+    local: Vec<Event>,
+    calendars: &Calendars,
+    cache: &mut EventCache,
+    prefetched: &mut BTreeMap<DateTime<Local>, AppsReadonly>,
+) {
+    let day = match day {
+        Ok(Ok(day)) => day,
+        Ok(Err(status)) => {
+            eprintln!("Error prefetching {}. http status: {:?}", date, status);
+            return;
+        }
+        Err(err) => {
+            eprintln!("Error prefetching {}: {:?}", date, err);
+            return;
+        }
+    };
+
+    let mut events = Appointments::new();
+    for (calendar_id, items) in day {
+        if let Err(err) = events.add(&items, calendars.tag(&calendar_id)) {
+            eprintln!("Error prefetching {}: {:?}", date, err);
+            return;
+        }
+    }
+    events.add_events(local);
+    let events = events.finalise();
+    trace!("prefetched {}", date);
+    cache.store(&date, &events);
+    prefetched.insert(date, events);
+}
+
+/// The events of the other sources alone, as read without a Google
+/// calendar.
+fn local_appointments(events: Vec<Event>) -> AppsReadonly {
+    let mut apps = Appointments::new();
+    apps.add_events(events);
+    apps.finalise()
+}
+
+/// Keeps a day read ahead whose reply arrives while another one is
+/// awaited, such as after a long press of reset.
+fn store_unawaited(
+    reply: Reply,
+    calendars: &Calendars,
+    cache: &mut EventCache,
+    prefetched: &mut BTreeMap<DateTime<Local>, AppsReadonly>,
+) {
+    match reply {
+        Reply::Day(date, day, local) => {
+            store_prefetched(date, day, local, calendars, cache, prefetched)
+        }
+        Reply::Local(date, local) => {
+            prefetched.insert(date, local_appointments(local));
+        }
+        _ => error!("unexpected reply from network worker"),
+    }
+}
+
+/// Waits for the next button poll, shutting down on a press of reset.
+fn await_shutdown(
+    reset_button: &mut LongPressButton,
+    gpio: &mut dyn Pins,
+    period: Duration,
+) -> Result<(), Error> {
+    thread::sleep(period);
+    if opt_filter(&reset_button.event(gpio)?, |e| e.is_short_press()) {
+        shutdown()?;
+    }
+    Ok(())
+}
+
+/// The events of date read ahead or saved. None when the day hasn't
+/// been read.
+fn day_events(
+    date: &DateTime<Local>,
+    calendars: &Calendars,
+    cache: &EventCache,
+    prefetched: &BTreeMap<DateTime<Local>, AppsReadonly>,
+) -> Option<AppsReadonly> {
+    let read_ahead = prefetched.get(date).cloned();
+    if calendars.google {
+        read_ahead.or_else(|| cache.day(date).map(|(apps, _downloaded)| apps))
+    } else {
+        read_ahead
+    }
+}
+
+/// Gives the renderer the other days that view shows alongside date.
+fn show_overview(
+    renderer: &mut Renderer,
+    view: View,
+    date: &DateTime<Local>,
+    calendars: &Calendars,
+    cache: &EventCache,
+    prefetched: &BTreeMap<DateTime<Local>, AppsReadonly>,
+) {
+    renderer.set_week(match view {
+        View::Week => Some(
+            (1..WEEK_DAYS)
+                .map(|offset| {
+                    let day = *date + chrono::Duration::days(offset);
+                    let apps = day_events(&day, calendars, cache, prefetched);
+                    (day, apps)
+                })
+                .collect(),
+        ),
+        _ => None,
+    });
+    renderer.set_month(match view {
+        View::Month => Some(
+            month_days(date)
+                .iter()
+                .map(|day| {
+                    day_events(day, calendars, cache, prefetched).map(|apps| apps.events().len())
+                })
+                .collect(),
+        ),
+        _ => None,
+    });
+    renderer.set_up_next(view == View::UpNext);
+}
+
+/// Every day of the month that date falls in.
+fn month_days(date: &DateTime<Local>) -> Vec<DateTime<Local>> {
+    let first = *date - chrono::Duration::days(i64::from(date.day0()));
+    (0..31)
+        .map(|offset| first + chrono::Duration::days(offset))
+        .take_while(|day| day.month() == date.month())
+        .collect()
+}
+
+/// The same day months later, or the last day of the month when it's
+/// shorter.
+fn add_months(date: &DateTime<Local>, months: i32) -> DateTime<Local> {
+    let index = date.year() * 12 + date.month0() as i32 + months;
+    let (year, month) = (index.div_euclid(12), index.rem_euclid(12) as u32 + 1);
+    let first = Local.ymd(year, month, 1).and_hms(0, 0, 0);
+    let last_day = month_days(&first).len() as u32;
+    Local
+        .ymd(year, month, date.day().min(last_day))
+        .and_hms(0, 0, 0)
+}
+
+/// The date the back or next button moves to from date.
+fn navigate(view: View, date: &DateTime<Local>, forward: bool) -> DateTime<Local> {
+    match (view, forward) {
+        (View::Month, true) => add_months(date, 1),
+        (View::Month, false) => add_months(date, -1),
+        (_, true) => *date + chrono::Duration::days(1),
+        (_, false) => *date - chrono::Duration::days(1),
+    }
+}
+
+/// The next day of the month selected in the month view, back to the
+/// first after the last.
+fn next_in_month(date: &DateTime<Local>) -> DateTime<Local> {
+    let next = *date + chrono::Duration::days(1);
+    if next.month() == date.month() {
+        next
+    } else {
+        month_days(date)[0]
+    }
+}
+
+/// The days worth reading ahead around date, in the reverse of the
+/// order they're read.
+fn days_to_prefetch(view: View, date: &DateTime<Local>) -> Vec<DateTime<Local>> {
+    match view {
+        View::Month => {
+            let mut days: Vec<DateTime<Local>> = month_days(date)
+                .into_iter()
+                .filter(|day| day != date)
+                .collect();
+            days.sort_by_key(|day| -(*day - *date).num_days().abs());
+            days
+        }
+        _ => {
+            let last = if view == View::Week { WEEK_DAYS - 1 } else { 1 };
+            let mut days = vec![*date - chrono::Duration::days(1)];
+            days.extend((1..=last).rev().map(|offset| *date + chrono::Duration::days(offset)));
+            days
+        }
+    }
+}
+
+/// Drops the days read ahead that aren't worth keeping once date is
+/// shown, and returns the days still to be read in the order they're
+/// read from the back. The month view keeps the months either side, so
+/// moving between months reads nothing again until a full refresh.
+fn plan_prefetch(
+    view: View,
+    date: &DateTime<Local>,
+    refresh_type: &RefreshType,
+    prefetched: &mut BTreeMap<DateTime<Local>, AppsReadonly>,
+) -> Vec<DateTime<Local>> {
+    let adjacent = days_to_prefetch(view, date);
+    let first = month_days(date)[0];
+    let (kept_from, kept_until) = (add_months(&first, -1), add_months(&first, 2));
+    prefetched.retain(|day, _| {
+        adjacent.contains(day) || (view == View::Month && *day >= kept_from && *day < kept_until)
+    });
+    adjacent
+        .into_iter()
+        .filter(|day| *refresh_type == RefreshType::Full || !prefetched.contains_key(day))
+        .collect()
+}
+
 pub fn render_stms() -> Result<(), Error> {
     let mut f = File::create("docs/cal_machine.dot")?;
     Machine::render_to(&mut f);
@@ -237,17 +487,22 @@ fn max_row_offset(num_event_rows: GlyphYCnt, screen_height: GlyphYCnt) -> GlyphY
     }
 }
 
-fn prev_v_pos(v_pos: GlyphYCnt) -> GlyphYCnt {
-    if V_POS_INC > v_pos.0 {
+fn prev_v_pos(v_pos: GlyphYCnt, scroll_rows: usize) -> GlyphYCnt {
+    if scroll_rows > v_pos.0 {
         GlyphYCnt(0)
     } else {
-        GlyphYCnt((v_pos.0 - V_POS_INC) as usize)
+        GlyphYCnt((v_pos.0 - scroll_rows) as usize)
     }
 }
 
-fn new_pos(v_pos: GlyphYCnt, num_event_rows: GlyphYCnt, screen_height: GlyphYCnt) -> GlyphYCnt {
+fn new_pos(
+    v_pos: GlyphYCnt,
+    num_event_rows: GlyphYCnt,
+    screen_height: GlyphYCnt,
+    scroll_rows: usize,
+) -> GlyphYCnt {
     let max_row_offset = max_row_offset(num_event_rows, screen_height);
-    let prev_v_pos = prev_v_pos(v_pos);
+    let prev_v_pos = prev_v_pos(v_pos, scroll_rows);
 
     if prev_v_pos.0 + screen_height.0 >= num_event_rows.0 {
         GlyphYCnt(0)
@@ -259,242 +514,351 @@ fn new_pos(v_pos: GlyphYCnt, num_event_rows: GlyphYCnt, screen_height: GlyphYCnt
 }
 
 //    loader: impl Fn() -> io::Result<Option<RefreshToken>>,
+/// Flags raised by the signal handlers in main.
+pub struct Signals<'a> {
+    /// Stop running once set.
+    pub quit: &'a AtomicBool,
+    /// Read the settings again once set.
+    pub reload: &'a AtomicBool,
+}
+
+fn long_press_button(pin: usize, timing: &Timing) -> LongPressButton {
+    LongPressButton::new(
+        Pin(pin),
+        DetectableDuration(timing.long_duration()),
+        LongReleaseDuration(timing.longish_duration()),
+    )
+}
+
+/// The settings and calendars currently on disk, or why they can't be
+/// used, in a form fit for the display.
+pub fn load_settings(var_dir: &Path) -> Result<(Settings, Calendars), String> {
+    let reloaded = Settings::load(var_dir).map_err(|err| err.message())?;
+    let calendars = reloaded
+        .calendars()
+        .map_err(|err| format!("Failed to read the calendars: {}", err))?;
+    Ok((reloaded, calendars))
+}
+
 pub fn run(
     renderer: &mut Renderer,
-    quitter: &Arc<AtomicBool>,
-    config_file: &Path,
-    saver: impl Fn(&RefreshToken, &mut Renderer) -> Result<(), Error>,
+    gpio: &mut dyn Pins,
+    signals: &Signals,
+    settings: &mut Settings,
+    calendars: &mut Calendars,
+    cache: &mut EventCache,
+    saver: impl Fn(Persisted, &mut Renderer) -> Result<(), Error>,
 ) -> Result<(), Error> {
     use Machine::{
-        CachedDisplay, DeviceAuthPoll, DisplayError, ErrorWait, LoadAuth, NetworkOutage,
-        PageEvents, PollEvents, ReadFirstEvents, RefreshAuth, RequestCodes,
+        AwaitCodes, AwaitEvents, AwaitLocalEvents, AwaitPoll, AwaitRefresh, CachedDisplay,
+        DeviceAuthPoll, DisplayError, ErrorWait, LoadAuth, LocalEvents, NetworkOutage, PageEvents,
+        PollEvents, ReadFirstEvents, ReadLocalEvents, RefreshAuth, RequestCodes,
     };
 
-    use reqwest::{Response, StatusCode};
+    use reqwest::StatusCode;
 
-    const HTTP_ERROR: &str = "HTTP error";
     const LOAD_FAILED: &str = "Failed to load credentials";
+    const CLIENT_LOAD_FAILED: &str = "Failed to load the client ID and secret from";
     const QUOTA_EXCEEDED: &str = "Quota Exceeded";
-    const ACCESS_DENIED: &str = "User has refused to grant access to this calendar";
-    const UNRECOGNISED_TOKEN_TYPE: &str = "Unrecognised token type";
-    const LONGISH_DURATION: Duration = Duration::from_millis(1500);
-    const LONG_DURATION: Duration = Duration::from_secs(4);
     const GLYPH_Y_ORIGIN: GlyphYCnt = GlyphYCnt(0);
+    const CLIENT_UNLOADED: &str = "client credentials are loaded by LoadAuth";
+
+    let var_dir = settings.var_dir.clone();
+    let mut recheck_period = settings.timing.recheck_period();
+    let mut time_update_period = settings.timing.time_update_period();
+    let mut button_poll_period = settings.timing.button_poll_period();
+    let mut scroll_rows = settings.buttons.scroll_rows;
 
     let mut today = Local::today().and_hms(0, 0, 0);
     let mut display_date = today; //don't delete this variable -- it's needed after a network outage to display events from that last date we navigated to, while at the same time reverting date changes due to the previous failed date navigation operation
     let mut v_pos: GlyphYCnt = GLYPH_Y_ORIGIN;
-    let retriever = EventRetriever::inst();
+    let worker = Worker::spawn(Endpoints::from_env()?);
+    //the other calendars are only read by the worker from here on
+    worker.submit(Job::Sources(calendars.take_sources()));
+    let mut client: Option<ClientCredentials> = None;
+    let mut synced = SyncedCalendars::new();
+    let mut google_ids: Vec<String> = Vec::new();
+    let mut prefetched: BTreeMap<DateTime<Local>, AppsReadonly> = BTreeMap::new();
+    let mut to_prefetch: Vec<DateTime<Local>> = Vec::new();
+    let mut prefetching = false;
+    let mut local_day: Option<(DateTime<Local>, Vec<Event>)> = None;
+    let mut navigated_to: Option<DateTime<Local>> = None;
+    let mut view = settings.view;
     let mut mach = Machine::new((), Box::new(|mach| {
         trace!("dropping cal_machine Machine: {:?}", mach);
         match mach {
             CalsAtEnd::LoadAuth(st) =>CalTerminals::LoadAuth(st),
             CalsAtEnd::ErrorWait(st) =>CalTerminals::ErrorWait(st),
             CalsAtEnd::RequestCodes(st) =>CalTerminals::RequestCodes(st),
+            CalsAtEnd::AwaitCodes(st) =>CalTerminals::AwaitCodes(st),
             CalsAtEnd::RefreshAuth(st) =>CalTerminals::RefreshAuth(st),
+            CalsAtEnd::AwaitRefresh(st) =>CalTerminals::AwaitRefresh(st),
             CalsAtEnd::ReadFirstEvents(st)=>CalTerminals::ReadFirstEvents(st),
             CalsAtEnd::DeviceAuthPoll(st) =>CalTerminals::DeviceAuthPoll(st),
+            CalsAtEnd::AwaitPoll(st) =>CalTerminals::AwaitPoll(st),
             CalsAtEnd::DisplayError(st)=>CalTerminals::DisplayError(st),
+            CalsAtEnd::AwaitEvents(st)=>CalTerminals::AwaitEvents(st),
             CalsAtEnd::PageEvents(st)=>CalTerminals::PageEvents(st),
             CalsAtEnd::PollEvents(st)=>CalTerminals::PollEvents(st),
             CalsAtEnd::CachedDisplay(st)=>CalTerminals::CachedDisplay(st),
             CalsAtEnd::NetworkOutage(st)=>CalTerminals::NetworkOutage(st),
+            CalsAtEnd::ReadLocalEvents(st)=>CalTerminals::ReadLocalEvents(st),
+            CalsAtEnd::AwaitLocalEvents(st)=>CalTerminals::AwaitLocalEvents(st),
+            CalsAtEnd::LocalEvents(st)=>CalTerminals::LocalEvents(st),
         }
     }));
-    let mut gpio = GPIO::new()?;
-    let mut reset_button = LongPressButton::new(
-        Pin(SW3_GPIO),
-        DetectableDuration(LONG_DURATION),
-        LongReleaseDuration(LONGISH_DURATION),
-    );
-    let mut back_button = LongPressButton::new(
-        Pin(SW4_GPIO),
-        DetectableDuration(LONG_DURATION),
-        LongReleaseDuration(LONGISH_DURATION),
-    );
-    let mut next_button = LongPressButton::new(
-        Pin(SW1_GPIO),
-        DetectableDuration(LONG_DURATION),
-        LongReleaseDuration(LONGISH_DURATION),
-    );
-    let mut scroll_button = LongPressButton::new(
-        Pin(SW2_GPIO),
-        DetectableDuration(LONG_DURATION),
-        LongReleaseDuration(LONGISH_DURATION),
-    );
-
-    while !quitter.load(AtomicOrdering::SeqCst) {
+    let mut reset_button = long_press_button(settings.buttons.reset, &settings.timing);
+    let mut back_button = long_press_button(settings.buttons.back, &settings.timing);
+    let mut next_button = long_press_button(settings.buttons.next, &settings.timing);
+    let mut scroll_button = long_press_button(settings.buttons.scroll, &settings.timing);
+
+    while !signals.quit.load(AtomicOrdering::SeqCst) {
+        if signals.reload.swap(false, AtomicOrdering::SeqCst) {
+            match load_settings(&settings.var_dir) {
+                Ok((reloaded, mut reloaded_calendars)) => {
+                    println!("reloaded {}", SETTINGS_FILE);
+                    //the machine only chooses between Google and local calendars when it starts
+                    reloaded_calendars.google = calendars.google;
+                    *calendars = reloaded_calendars;
+                    worker.submit(Job::Sources(calendars.take_sources()));
+                    prefetched.clear();
+
+                    if reloaded.timing != settings.timing || reloaded.buttons != settings.buttons {
+                        reset_button = long_press_button(reloaded.buttons.reset, &reloaded.timing);
+                        back_button = long_press_button(reloaded.buttons.back, &reloaded.timing);
+                        next_button = long_press_button(reloaded.buttons.next, &reloaded.timing);
+                        scroll_button = long_press_button(reloaded.buttons.scroll, &reloaded.timing);
+                    }
+                    recheck_period = reloaded.timing.recheck_period();
+                    time_update_period = reloaded.timing.time_update_period();
+                    button_poll_period = reloaded.timing.button_poll_period();
+                    scroll_rows = reloaded.buttons.scroll_rows;
+
+                    if reloaded.view != settings.view {
+                        //shown from the next time the events are read
+                        view = reloaded.view;
+                        v_pos = GLYPH_Y_ORIGIN;
+                    }
+
+                    let layout = reloaded.layout(renderer.panel());
+                    let turned = reloaded.rotation != settings.rotation;
+                    if turned || layout != settings.layout(renderer.panel()) {
+                        renderer.set_rotation(reloaded.rotation());
+                        renderer.set_layout(layout);
+                        v_pos = GLYPH_Y_ORIGIN;
+                        renderer.redraw(Now(Local::now()), |_, _| GLYPH_Y_ORIGIN)?;
+                    }
+                    *settings = reloaded;
+                }
+                Err(message) => {
+                    error!("keeping previous settings: {}", message);
+                    renderer.display_notice(&message)?;
+                }
+            }
+        }
+
         mach = match mach {
-            LoadAuth(st) => match RefreshToken::load(&config_file) {
+            LoadAuth(st) if !calendars.google => {
+                ReadLocalEvents(st.into(), RefreshType::Full, PendingDisplayDate(today))
+            }
+            LoadAuth(st) => match ClientCredentials::load(&var_dir.join(CLIENT_SECRET_FILE)) {
                 Err(error_msg) => DisplayError(
                     st.into(),
-                    format!("{}: {}", LOAD_FAILED, error_msg.to_string()),
+                    format!(
+                        "{} {}: {}",
+                        CLIENT_LOAD_FAILED,
+                        var_dir.join(CLIENT_SECRET_FILE).display(),
+                        error_msg
+                    ),
                 ),
-                Ok(None) => RequestCodes(st.into()),
-                Ok(Some(refresh_token)) => {
-                    RefreshAuth(st.into(), refresh_token, PendingDisplayDate(today))
+                Ok(credentials) => {
+                    client = Some(credentials);
+                    match RefreshToken::load(&var_dir.join(REFRESH_TOKEN_FILE)) {
+                        Err(error_msg) => DisplayError(
+                            st.into(),
+                            format!("{}: {}", LOAD_FAILED, error_msg.to_string()),
+                        ),
+                        Ok(None) => RequestCodes(st.into()),
+                        Ok(Some(refresh_token)) => {
+                            RefreshAuth(st.into(), refresh_token, PendingDisplayDate(today))
+                        }
+                    }
                 }
             },
             RequestCodes(st) => {
-                let mut resp: Response = retriever.retrieve_dev_and_code()?;
-                let status = resp.status();
-                match status {
-                    StatusCode::OK => {
-                        let body: DeviceUserCodeResponse = resp.json()?;
-                        println!("Body is next... {:?}", body);
-                        renderer.display_user_code(
-                            &body.user_code,
-                            &(Local::now() + chrono::Duration::seconds(body.expires_in)),
-                            &body.verification_url,
-                        )?;
+                worker.submit(Job::DeviceCode(client.clone().expect(CLIENT_UNLOADED)));
+                AwaitCodes(st.into())
+            }
+            AwaitCodes(st) => match worker.reply() {
+                Some(Reply::DeviceCode(Ok(CodeOutcome::Codes(body)))) => {
+                    println!("Body is next... {:?}", body);
+                    renderer.display_user_code(
+                        &body.user_code,
+                        &(Local::now() + chrono::Duration::seconds(body.expires_in)),
+                        &body.verification_url,
+                    )?;
 
-                        DeviceAuthPoll(
-                            st.into(),
-                            String::from(body.device_code),
-                            body.interval as PeriodSeconds,
-                        )
-                    }
-                    other_status => {
-                        let body: DeviceUserCodeErrorResponse = resp.json()?;
-                        eprintln!("Error when getting request code: {:?}", body);
-                        match other_status {
-                            StatusCode::FORBIDDEN
-                                if body.error_code == QUOTA_EXCEEDED_ERROR_CODE =>
-                            {
-                                DisplayError(st.into(), QUOTA_EXCEEDED.to_string())
-                            }
-                            _otherwise => DisplayError(
-                                st.into(),
-                                format!(
-                                    "{}: {}, {}",
-                                    HTTP_ERROR,
-                                    other_status.as_u16(),
-                                    body.error_code
-                                ),
-                            ),
+                    DeviceAuthPoll(
+                        st.into(),
+                        body.device_code,
+                        body.interval as PeriodSeconds,
+                        PolledAt::now(),
+                    )
+                }
+                Some(Reply::DeviceCode(Ok(CodeOutcome::Refused(status, body)))) => {
+                    eprintln!("Error when getting request code: {:?}", body);
+                    match status {
+                        StatusCode::FORBIDDEN if body.error_code == QUOTA_EXCEEDED_ERROR_CODE => {
+                            DisplayError(st.into(), QUOTA_EXCEEDED.to_string())
                         }
+                        _otherwise => DisplayError(
+                            st.into(),
+                            format!("{}: {}, {}", HTTP_ERROR, status.as_u16(), body.error_code),
+                        ),
                     }
                 }
-            }
-            RefreshAuth(st, RefreshToken(refresh_token), pending_display_date) => {
+                Some(Reply::DeviceCode(Err(err))) => {
+                    DisplayError(st.into(), format!("{}: {}", HTTP_ERROR, err))
+                }
+                Some(reply) => {
+                    prefetching = false;
+                    store_unawaited(reply, calendars, cache, &mut prefetched);
+                    AwaitCodes(st)
+                }
+                None => {
+                    await_shutdown(&mut reset_button, gpio, button_poll_period)?;
+                    AwaitCodes(st)
+                }
+            },
+            RefreshAuth(st, refresh_token, pending_display_date) => {
                 renderer.display_status(Status::NetworkPending, true)?;
-                match retriever.refresh(&refresh_token) {
-                    Ok(mut resp) => {
-                        let status = resp.status();
-                        match status {
-                            StatusCode::OK => {
-                                let credentials_tokens: RefreshResponse = resp.json()?;
-
-                                let token_type = credentials_tokens.token_type.clone();
-                                if token_type != TOKEN_TYPE {
-                                    DisplayError(
-                                        st.into(),
-                                        format!("{}: {}", UNRECOGNISED_TOKEN_TYPE, token_type),
-                                    )
-                                } else {
-                                    println!("Body is next... {:?}", credentials_tokens);
-                                    let credentials: Authenticators =
-                                        (RefreshToken(refresh_token.clone()), credentials_tokens)
-                                            .into();
-                                    ReadFirstEvents(
-                                        st.into(),
-                                        credentials,
-                                        RefreshedAt::now(),
-                                        RefreshType::Full,
-                                        pending_display_date,
-                                    )
-                                }
-                            }
-                            StatusCode::BAD_REQUEST => {
-                                RequestCodes(st.into())
-                            }
-                            StatusCode::UNAUTHORIZED => {
-                                RequestCodes(st.into())
-                            }
-                            other_status => {
-                                let err_msg = format!("When refreshing status: {:?}", other_status);
-                                DisplayError(st.into(), err_msg)
-                            }
-                        }
-                    }
-
-                    Err(err) => {
-                        eprintln!("Error refreshing: {:?}", err);
-                        CachedDisplay(
+                worker.submit(Job::Refresh(
+                    client.clone().expect(CLIENT_UNLOADED),
+                    refresh_token.0.clone(),
+                ));
+                AwaitRefresh(st.into(), refresh_token, pending_display_date)
+            }
+            AwaitRefresh(st, refresh_token, pending_display_date) => match worker.reply() {
+                Some(Reply::Refresh(Ok(Ok(credentials_tokens)))) => {
+                    let token_type = credentials_tokens.token_type.clone();
+                    if token_type != TOKEN_TYPE {
+                        DisplayError(
                             st.into(),
-                            RefreshToken(refresh_token),
-                            LastNetErrorAt::now(),
+                            format!("{}: {}", UNRECOGNISED_TOKEN_TYPE, token_type),
+                        )
+                    } else {
+                        println!("Body is next... {:?}", credentials_tokens);
+                        let credentials: Authenticators =
+                            (refresh_token, credentials_tokens).into();
+                        ReadFirstEvents(
+                            st.into(),
+                            credentials,
+                            RefreshedAt::now(),
+                            RefreshType::Full,
+                            pending_display_date,
                         )
                     }
                 }
+                Some(Reply::Refresh(Ok(Err(StatusCode::BAD_REQUEST)))) => RequestCodes(st.into()),
+                Some(Reply::Refresh(Ok(Err(StatusCode::UNAUTHORIZED)))) => RequestCodes(st.into()),
+                Some(Reply::Refresh(Ok(Err(other_status)))) => {
+                    let err_msg = format!("When refreshing status: {:?}", other_status);
+                    DisplayError(st.into(), err_msg)
+                }
+                Some(Reply::Refresh(Err(err))) => {
+                    eprintln!("Error refreshing: {:?}", err);
+                    CachedDisplay(st.into(), refresh_token, LastNetErrorAt::now())
+                }
+                Some(reply) => {
+                    prefetching = false;
+                    store_unawaited(reply, calendars, cache, &mut prefetched);
+                    AwaitRefresh(st, refresh_token, pending_display_date)
+                }
+                None => {
+                    await_shutdown(&mut reset_button, gpio, button_poll_period)?;
+                    AwaitRefresh(st, refresh_token, pending_display_date)
+                }
+            },
+            DeviceAuthPoll(st, device_code, delay_s, polled_at) => {
+                if polled_at.as_ref().elapsed() >= Duration::from_secs(delay_s) {
+                    worker.submit(Job::Poll(
+                        client.clone().expect(CLIENT_UNLOADED),
+                        device_code.clone(),
+                    ));
+                    AwaitPoll(st.into(), device_code, delay_s)
+                } else {
+                    await_shutdown(&mut reset_button, gpio, button_poll_period)?;
+                    DeviceAuthPoll(st, device_code, delay_s, polled_at)
+                }
             }
-            DeviceAuthPoll(st, device_code, delay_s) => {
-                thread::sleep(Duration::from_secs(delay_s));
-                let mut resp: Response = retriever.poll(&device_code)?;
-                let status = resp.status();
-                match status {
-                    StatusCode::OK => {
-                        let credentials_tokens: PollResponse = resp.json()?;
-
-                        let token_type = credentials_tokens.token_type.clone();
-                        if token_type != TOKEN_TYPE {
-                            DisplayError(
-                                st.into(),
-                                format!("{}: {}", UNRECOGNISED_TOKEN_TYPE, token_type),
-                            )
-                        } else {
-                            println!("Body is next... {:?}", credentials_tokens);
-                            let auth: Authenticators = credentials_tokens.into();
-                            saver(&auth.refresh_token, renderer)?;
-                            ReadFirstEvents(
-                                st.into(),
-                                auth,
-                                RefreshedAt::now(),
-                                RefreshType::Full,
-                                PendingDisplayDate(today),
-                            )
-                        }
+            AwaitPoll(st, device_code, delay_s) => match worker.reply() {
+                Some(Reply::Poll(Ok(PollOutcome::Tokens(credentials_tokens)))) => {
+                    let token_type = credentials_tokens.token_type.clone();
+                    if token_type != TOKEN_TYPE {
+                        DisplayError(
+                            st.into(),
+                            format!("{}: {}", UNRECOGNISED_TOKEN_TYPE, token_type),
+                        )
+                    } else {
+                        println!("Body is next... {:?}", credentials_tokens);
+                        let auth: Authenticators = credentials_tokens.into();
+                        saver(Persisted::RefreshToken(&auth.refresh_token), renderer)?;
+                        ReadFirstEvents(
+                            st.into(),
+                            auth,
+                            RefreshedAt::now(),
+                            RefreshType::Full,
+                            PendingDisplayDate(today),
+                        )
                     }
-                    other_status => match resp.json::<PollErrorResponse>() {
-                        Ok(body) => {
-                            eprintln!("Error when polling: {:?}", body);
-                            match other_status {
-                                StatusCode::FORBIDDEN if body.error == ACCESS_DENIED_ERROR => {
-                                    DisplayError(st.into(), ACCESS_DENIED.to_string())
-                                }
-                                StatusCode::BAD_REQUEST
-                                    if body.error == AUTHORISATION_PENDING_ERROR =>
-                                {
-                                    DeviceAuthPoll(st, device_code, delay_s)
-                                }
-                                StatusCode::PRECONDITION_REQUIRED
-                                    if body.error == AUTHORISATION_PENDING_ERROR =>
-                                {
-                                    DeviceAuthPoll(st, device_code, delay_s)
-                                }
-                                StatusCode::TOO_MANY_REQUESTS
-                                    if body.error == POLLING_TOO_FREQUENTLY_ERROR =>
-                                {
-                                    DeviceAuthPoll(st, device_code, delay_s * 2)
-                                }
-                                _otherwise => DisplayError(
-                                    st.into(),
-                                    format!(
-                                        "HTTP error: {}, {}, {}",
-                                        other_status.as_u16(),
-                                        body.error,
-                                        body.error_description
-                                    ),
-                                ),
-                            }
+                }
+                Some(Reply::Poll(Ok(PollOutcome::Refused(other_status, body)))) => {
+                    eprintln!("Error when polling: {:?}", body);
+                    match other_status {
+                        StatusCode::FORBIDDEN if body.error == ACCESS_DENIED_ERROR => {
+                            DisplayError(st.into(), ACCESS_DENIED.to_string())
+                        }
+                        StatusCode::BAD_REQUEST if body.error == AUTHORISATION_PENDING_ERROR => {
+                            DeviceAuthPoll(st.into(), device_code, delay_s, PolledAt::now())
                         }
-                        Err(error) => DisplayError(
+                        StatusCode::PRECONDITION_REQUIRED
+                            if body.error == AUTHORISATION_PENDING_ERROR =>
+                        {
+                            DeviceAuthPoll(st.into(), device_code, delay_s, PolledAt::now())
+                        }
+                        StatusCode::TOO_MANY_REQUESTS
+                            if body.error == POLLING_TOO_FREQUENTLY_ERROR =>
+                        {
+                            DeviceAuthPoll(st.into(), device_code, delay_s * 2, PolledAt::now())
+                        }
+                        _otherwise => DisplayError(
                             st.into(),
-                            format!("HTTP error: {}, {:?}", other_status.as_u16(), error),
+                            format!(
+                                "HTTP error: {}, {}, {}",
+                                other_status.as_u16(),
+                                body.error,
+                                body.error_description
+                            ),
                         ),
-                    },
+                    }
                 }
-            }
+                Some(Reply::Poll(Ok(PollOutcome::Unreadable(other_status, error)))) => DisplayError(
+                    st.into(),
+                    format!("HTTP error: {}, {:?}", other_status.as_u16(), error),
+                ),
+                Some(Reply::Poll(Err(err))) => {
+                    DisplayError(st.into(), format!("{}: {}", HTTP_ERROR, err))
+                }
+                Some(reply) => {
+                    prefetching = false;
+                    store_unawaited(reply, calendars, cache, &mut prefetched);
+                    AwaitPoll(st, device_code, delay_s)
+                }
+                None => {
+                    await_shutdown(&mut reset_button, gpio, button_poll_period)?;
+                    AwaitPoll(st, device_code, delay_s)
+                }
+            },
             ReadFirstEvents(
                 st,
                 credentials_tokens,
@@ -502,67 +866,33 @@ pub fn run(
                 refresh_type,
                 pending_display_date,
             ) => {
+                local_day = None;
                 renderer.display_status(Status::NetworkPending, true)?;
-                match retriever.read(
-                    &format!("Bearer {}", credentials_tokens.volatiles.access_token),
-                    &pending_display_date.0,
-                    &(*pending_display_date.as_ref() + chrono::Duration::days(1)
-                        - chrono::Duration::seconds(1)),
-                    &Option::<PageToken>::None,
-                ) {
-                    Ok(mut resp) => {
-                        let status = resp.status();
-                        match status {
-                            StatusCode::OK => {
-                                let events_resp: EventsResponse = resp.json()?;
-                                let mut new_events = evs::Appointments::new();
-                                new_events.add(&events_resp)?;
-                                let page_token = match events_resp.next_page_token {
-                                    None => None,
-                                    Some(next_page) => Some(PageToken(next_page)),
-                                };
-                                PageEvents(
-                                    st.into(),
-                                    credentials_tokens,
-                                    page_token,
-                                    new_events,
-                                    refreshed_at,
-                                    DownloadedAt::now(),
-                                    refresh_type,
-                                    pending_display_date,
-                                )
-                            }
-                            _other_status => {
-                                println!("Event Headers: {:#?}", resp.headers());
-                                println!("Event is next... {:?}", resp.text()?);
-                                DisplayError(
-                                    st.into(),
-                                    format!("in readfirst. http status: {:?}", status),
-                                )
-                            }
-                        }
-                    }
-                    Err(err) => {
-                        eprintln!("Error refreshing: {:?}", err);
-                        CachedDisplay(
-                            st.into(),
-                            credentials_tokens.refresh_token,
-                            LastNetErrorAt::now(),
-                        )
-                    }
-                }
+                worker.submit(Job::CalendarIds(
+                    credentials_tokens.bearer(),
+                    calendars.google_calendars.clone(),
+                ));
+                AwaitEvents(
+                    st.into(),
+                    credentials_tokens,
+                    Vec::new(),
+                    refreshed_at,
+                    DownloadedAt::now(),
+                    refresh_type,
+                    pending_display_date,
+                    TimeUpdatedAt::now(),
+                )
             }
             PageEvents(
                 st,
                 credentials_tokens,
-                page_token,
-                mut events,
+                mut pages,
                 refreshed_at,
                 downloaded_at,
                 refresh_type,
                 pending_display_date,
             ) => {
-                if let None = page_token {
+                if pages.is_empty() {
                     let now = Local::now();
                     let new_today = Local::today().and_hms(0, 0, 0);
                     if new_today != today && new_today != pending_display_date.0 {
@@ -571,18 +901,44 @@ pub fn run(
                             credentials_tokens.refresh_token,
                             PendingDisplayDate(new_today),
                         )
+                    } else if !opt_filter(&local_day, |(date, _)| *date == pending_display_date.0) {
+                        //the other sources are read once the Google calendars have been
+                        worker.submit(Job::Local(
+                            pending_display_date.0,
+                            refresh_type == RefreshType::Full,
+                        ));
+                        AwaitEvents(
+                            st.into(),
+                            credentials_tokens,
+                            pages,
+                            refreshed_at,
+                            downloaded_at,
+                            refresh_type,
+                            pending_display_date,
+                            TimeUpdatedAt::now(),
+                        )
                     } else {
                         today = new_today;
                         display_date = pending_display_date.0;
+                        let mut events =
+                            synced.appointments(&display_date, |id| calendars.tag(id))?;
+                        if let Some((_date, local)) = local_day.take() {
+                            events.add_events(local);
+                        }
+                        let events = events.finalise();
+                        cache.store(&display_date, &events);
+                        to_prefetch =
+                            plan_prefetch(view, &display_date, &refresh_type, &mut prefetched);
+                        show_overview(renderer, view, &display_date, calendars, cache, &prefetched);
                         println!("PageEvents. before display {:?}", v_pos);
                         let pos_calculator =
                             |num_event_rows: GlyphYCnt, screen_height: GlyphYCnt| {
-                                v_pos = new_pos(v_pos, num_event_rows, screen_height);
+                                v_pos = new_pos(v_pos, num_event_rows, screen_height, scroll_rows);
                                 v_pos
                             };
                         renderer.display_events(
                             display_date.clone(),
-                            events.finalise(),
+                            events,
                             refresh_type,
                             Now(now),
                             pos_calculator,
@@ -598,57 +954,212 @@ pub fn run(
                         )
                     }
                 } else {
+                    let page = pages.pop().expect("PageEvents. no calendar pages left");
                     renderer.display_status(Status::NetworkPending, true)?;
-                    match retriever.read(
-                        &format!("Bearer {}", credentials_tokens.volatiles.access_token),
+                    worker.submit(Job::Page(
+                        credentials_tokens.bearer(),
+                        pending_display_date.0,
+                        page,
+                        calendars.google_page_size,
+                    ));
+                    AwaitEvents(
+                        st.into(),
+                        credentials_tokens,
+                        pages,
+                        refreshed_at,
+                        downloaded_at,
+                        refresh_type,
+                        pending_display_date,
+                        TimeUpdatedAt::now(),
+                    )
+                }
+            }
+            AwaitEvents(
+                st,
+                credentials_tokens,
+                mut pages,
+                refreshed_at,
+                downloaded_at,
+                refresh_type,
+                pending_display_date,
+                time_updated_at,
+            ) => match worker.reply() {
+                Some(Reply::Day(date, day, local)) => {
+                    prefetching = false;
+                    store_prefetched(date, day, local, calendars, cache, &mut prefetched);
+                    AwaitEvents(
+                        st,
+                        credentials_tokens,
+                        pages,
+                        refreshed_at,
+                        downloaded_at,
+                        refresh_type,
+                        pending_display_date,
+                        time_updated_at,
+                    )
+                }
+                Some(_) if navigated_to.is_some() => {
+                    //the date was changed while downloading so the reply is no longer wanted
+                    let new_display_date = navigated_to.take().expect("AwaitEvents. no date");
+                    v_pos = GLYPH_Y_ORIGIN;
+                    ReadFirstEvents(
+                        st.into(),
+                        credentials_tokens,
+                        refreshed_at,
+                        RefreshType::Partial,
+                        PendingDisplayDate(new_display_date),
+                    )
+                }
+                Some(Reply::Local(date, local)) => {
+                    local_day = Some((date, local));
+                    PageEvents(
+                        st.into(),
+                        credentials_tokens,
+                        pages,
+                        refreshed_at,
+                        downloaded_at,
+                        refresh_type,
+                        pending_display_date,
+                    )
+                }
+                Some(Reply::CalendarIds(Ok(Ok(ids)))) => {
+                    synced.retain(&ids);
+                    google_ids = ids.clone();
+                    let pages = ids
+                        .into_iter()
+                        .rev()
+                        .map(|calendar_id| {
+                            let sync_token =
+                                synced.sync_token(&calendar_id, &pending_display_date.0);
+                            CalendarPage::first(calendar_id, sync_token)
+                        })
+                        .collect();
+                    PageEvents(
+                        st.into(),
+                        credentials_tokens,
+                        pages,
+                        refreshed_at,
+                        downloaded_at,
+                        refresh_type,
+                        pending_display_date,
+                    )
+                }
+                Some(Reply::CalendarIds(Ok(Err(status)))) => DisplayError(
+                    st.into(),
+                    format!("in readfirst. calendar list http status: {:?}", status),
+                ),
+                Some(Reply::Page(page, Ok(PageOutcome::Events(events_resp)))) => {
+                    synced.update(
+                        &page.calendar_id,
                         &pending_display_date.0,
-                        &(pending_display_date.0 + chrono::Duration::days(1)
-                            - chrono::Duration::seconds(1)),
-                        &page_token,
-                    ) {
-                        Ok(mut resp) => {
-                            let status = resp.status();
-                            match status {
-                                StatusCode::OK => {
-                                    let events_resp: EventsResponse = resp.json()?;
-                                    events.add(&events_resp)?;
-                                    let page_token = match events_resp.next_page_token {
-                                        None => None,
-                                        Some(next_page) => Some(PageToken(next_page)),
-                                    };
+                        page.page_token.is_none() && page.sync_token.is_none(),
+                        &events_resp,
+                    )?;
+                    if let Some(next_page) = events_resp.next_page_token {
+                        pages.push(CalendarPage {
+                            page_token: Some(PageToken(next_page)),
+                            ..page
+                        });
+                    }
 
-                                    PageEvents(
-                                        st,
-                                        credentials_tokens,
-                                        page_token,
-                                        events,
-                                        refreshed_at,
-                                        downloaded_at,
-                                        refresh_type,
-                                        pending_display_date,
-                                    )
-                                }
-                                _other_status => {
-                                    println!("Event Headers: {:#?}", resp.headers());
-                                    println!("Event is next... {:?}", resp.text()?);
-                                    DisplayError(
-                                        st.into(),
-                                        format!("in readfirst. http status: {:?}", status),
-                                    )
-                                }
-                            }
-                        }
-                        Err(err) => {
-                            eprintln!("Error refreshing: {:?}", err);
-                            CachedDisplay(
-                                st.into(),
-                                credentials_tokens.refresh_token,
-                                LastNetErrorAt::now(),
-                            )
-                        }
+                    PageEvents(
+                        st.into(),
+                        credentials_tokens,
+                        pages,
+                        refreshed_at,
+                        downloaded_at,
+                        refresh_type,
+                        pending_display_date,
+                    )
+                }
+                Some(Reply::Page(page, Ok(PageOutcome::Gone))) => {
+                    println!("sync token expired, reading {} in full", page.calendar_id);
+                    synced.forget(&page.calendar_id);
+                    pages.push(CalendarPage::first(page.calendar_id, None));
+                    PageEvents(
+                        st.into(),
+                        credentials_tokens,
+                        pages,
+                        refreshed_at,
+                        downloaded_at,
+                        refresh_type,
+                        pending_display_date,
+                    )
+                }
+                Some(Reply::Page(_, Ok(PageOutcome::Failed(status)))) => DisplayError(
+                    st.into(),
+                    format!("in readfirst. http status: {:?}", status),
+                ),
+                Some(Reply::CalendarIds(Err(err))) | Some(Reply::Page(_, Err(err))) => {
+                    eprintln!("Error refreshing: {:?}", err);
+                    CachedDisplay(
+                        st.into(),
+                        credentials_tokens.refresh_token,
+                        LastNetErrorAt::now(),
+                    )
+                }
+                Some(Reply::DeviceCode(_)) | Some(Reply::Poll(_)) | Some(Reply::Refresh(_)) => {
+                    error!("AwaitEvents. unexpected reply from network worker");
+                    AwaitEvents(
+                        st,
+                        credentials_tokens,
+                        pages,
+                        refreshed_at,
+                        downloaded_at,
+                        refresh_type,
+                        pending_display_date,
+                        time_updated_at,
+                    )
+                }
+                None => {
+                    thread::sleep(button_poll_period);
+                    let reset_event = reset_button.event(gpio)?;
+                    let back_event = back_button.event(gpio)?;
+                    let next_event = next_button.event(gpio)?;
+                    let scroll_event = scroll_button.event(gpio)?;
+
+                    let short_check = |e: &LongButtonEvent| e.is_short_press();
+
+                    let mut time_updated_at = time_updated_at;
+                    if opt_filter(&reset_event, short_check) {
+                        shutdown()?;
+                    } else if opt_filter(&scroll_event, short_check) {
+                        v_pos = GlyphYCnt(v_pos.0 + scroll_rows);
+                        let pos_calculator =
+                            |num_event_rows: GlyphYCnt, screen_height: GlyphYCnt| {
+                                v_pos = new_pos(v_pos, num_event_rows, screen_height, scroll_rows);
+                                v_pos
+                            };
+                        renderer.scroll_events(Now(Local::now()), pos_calculator)?;
+                    } else if opt_filter(&back_event, short_check)
+                        || opt_filter(&next_event, short_check)
+                    {
+                        let from = navigated_to.unwrap_or(pending_display_date.0);
+                        let new_display_date = if opt_filter(&back_event, short_check) {
+                            navigate(view, &from, false)
+                        } else {
+                            navigate(view, &from, true)
+                        };
+                        renderer.refresh_date(&new_display_date)?;
+                        navigated_to = Some(new_display_date);
+                    } else if time_updated_at.as_ref().elapsed() >= time_update_period {
+                        let pos_calculator =
+                            |_num_event_rows: GlyphYCnt, _screen_height: GlyphYCnt| v_pos;
+                        renderer.scroll_events(Now(Local::now()), pos_calculator)?;
+                        time_updated_at = TimeUpdatedAt::now();
                     }
+                    AwaitEvents(
+                        st,
+                        credentials_tokens,
+                        pages,
+                        refreshed_at,
+                        downloaded_at,
+                        refresh_type,
+                        pending_display_date,
+                        time_updated_at,
+                    )
                 }
-            }
+            },
             PollEvents(
                 st,
                 credentials,
@@ -657,6 +1168,29 @@ pub fn run(
                 time_updated_at,
                 pending_display_date,
             ) => {
+                if let Some(reply) = worker.reply() {
+                    match reply {
+                        Reply::Day(date, day, local) => {
+                            prefetching = false;
+                            store_prefetched(date, day, local, calendars, cache, &mut prefetched);
+                            if view != View::Day && pending_display_date.0 == display_date {
+                                show_overview(
+                                    renderer,
+                                    view,
+                                    &display_date,
+                                    calendars,
+                                    cache,
+                                    &prefetched,
+                                );
+                                let pos_calculator =
+                                    |_num_event_rows: GlyphYCnt, _screen_height: GlyphYCnt| v_pos;
+                                renderer.scroll_events(Now(Local::now()), pos_calculator)?;
+                            }
+                        }
+                        _ => error!("PollEvents. unexpected reply from network worker"),
+                    }
+                }
+
                 let waiting_for = started_wait_at.as_ref().elapsed();
                 let same_time_for = time_updated_at.as_ref().elapsed();
                 let elapsed_since_token_refresh = refreshed_at.as_ref().elapsed();
@@ -676,11 +1210,11 @@ pub fn run(
                 {
                     RefreshAuth(st.into(), credentials.refresh_token, pending_display_date)
                 } else {
-                    thread::sleep(BUTTON_POLL_PERIOD);
-                    let reset_event = reset_button.event(&mut gpio)?;
-                    let back_event = back_button.event(&mut gpio)?;
-                    let next_event = next_button.event(&mut gpio)?;
-                    let scroll_event = scroll_button.event(&mut gpio)?;
+                    thread::sleep(button_poll_period);
+                    let reset_event = reset_button.event(gpio)?;
+                    let back_event = back_button.event(gpio)?;
+                    let next_event = next_button.event(gpio)?;
+                    let scroll_event = scroll_button.event(gpio)?;
 
                     let short_check = |e: &LongButtonEvent| e.is_short_press();
                     let release_check = |e: &LongButtonEvent| e.is_release();
@@ -698,6 +1232,31 @@ pub fn run(
                             time_updated_at,
                             pending_display_date,
                         )
+                    } else if opt_filter(&next_event, long_check) {
+                        //the events are read again once the button is released
+                        view = view.next();
+                        v_pos = GLYPH_Y_ORIGIN;
+                        println!("PollEvents. switched to the {:?} view", view);
+                        PollEvents(
+                            st,
+                            credentials,
+                            refreshed_at,
+                            started_wait_at,
+                            time_updated_at,
+                            pending_display_date,
+                        )
+                    } else if view == View::Month && opt_filter(&back_event, long_check) {
+                        //the selected day is read once the button is released
+                        view = View::Day;
+                        v_pos = GLYPH_Y_ORIGIN;
+                        PollEvents(
+                            st,
+                            credentials,
+                            refreshed_at,
+                            started_wait_at,
+                            time_updated_at,
+                            pending_display_date,
+                        )
                     } else if opt_filter(&scroll_event, long_check) {
                         println!("full display & date refresh");
                         ReadFirstEvents(
@@ -707,12 +1266,23 @@ pub fn run(
                             RefreshType::Full,
                             PendingDisplayDate(Local::today().and_hms(0, 0, 0)),
                         )
+                    } else if view == View::Month && opt_filter(&scroll_event, short_check) {
+                        display_date = next_in_month(&pending_display_date.0);
+                        renderer.select_date(display_date, Now(Local::now()))?;
+                        PollEvents(
+                            st,
+                            credentials,
+                            refreshed_at,
+                            started_wait_at,
+                            time_updated_at,
+                            PendingDisplayDate(display_date),
+                        )
                     } else if opt_filter(&scroll_event, short_check) {
                         println!("PollEvents. before scroll v_pos: {:?}", v_pos);
-                        v_pos = GlyphYCnt(v_pos.0 + V_POS_INC).into();
+                        v_pos = GlyphYCnt(v_pos.0 + scroll_rows).into();
                         let pos_calculator =
                             |num_event_rows: GlyphYCnt, screen_height: GlyphYCnt| {
-                                v_pos = new_pos(v_pos, num_event_rows, screen_height);
+                                v_pos = new_pos(v_pos, num_event_rows, screen_height, scroll_rows);
                                 v_pos
                             };
                         renderer.scroll_events(Now(Local::now()), pos_calculator)?;
@@ -725,7 +1295,21 @@ pub fn run(
                             time_updated_at,
                             pending_display_date,
                         )
-                    } else if waiting_for >= RECHECK_PERIOD {
+                    } else if cache.snapshot_due() {
+                        //the snapshot only saves downloading again after a
+                        //restart so failing to write it isn't fatal
+                        if let Err(err) = saver(Persisted::Events(cache), renderer) {
+                            error!("failed to snapshot the event cache: {:?}", err);
+                        }
+                        cache.snapshotted();
+                        ReadFirstEvents(
+                            st.into(),
+                            credentials,
+                            refreshed_at,
+                            RefreshType::Full,
+                            pending_display_date,
+                        )
+                    } else if waiting_for >= recheck_period {
                         println!("full display refresh due");
                         ReadFirstEvents(
                             st.into(),
@@ -734,7 +1318,7 @@ pub fn run(
                             RefreshType::Full,
                             pending_display_date,
                         )
-                    } else if same_time_for >= TIME_UPDATE_PERIOD {
+                    } else if same_time_for >= time_update_period {
                         println!("time update due");
                         let pos_calculator =
                             |_num_event_rows: GlyphYCnt, _screen_height: GlyphYCnt| v_pos;
@@ -762,9 +1346,41 @@ pub fn run(
                             RefreshType::Partial,
                             pending_display_date,
                         )
-                    } else if opt_filter(&back_event, short_check) {
-                        let new_display_date = pending_display_date.0 - chrono::Duration::days(1);
-                        renderer.refresh_date(&new_display_date)?;
+                    } else if opt_filter(&back_event, short_check)
+                        || opt_filter(&next_event, short_check)
+                    {
+                        let new_display_date = if opt_filter(&back_event, short_check) {
+                            navigate(view, &pending_display_date.0, false)
+                        } else {
+                            navigate(view, &pending_display_date.0, true)
+                        };
+                        match prefetched.get(&new_display_date) {
+                            Some(events) => {
+                                //the events are read again once the button is released
+                                v_pos = GLYPH_Y_ORIGIN;
+                                show_overview(
+                                    renderer,
+                                    view,
+                                    &new_display_date,
+                                    calendars,
+                                    cache,
+                                    &prefetched,
+                                );
+                                let pos_calculator =
+                                    |num_event_rows: GlyphYCnt, screen_height: GlyphYCnt| {
+                                        v_pos = new_pos(v_pos, num_event_rows, screen_height, scroll_rows);
+                                        v_pos
+                                    };
+                                renderer.display_events(
+                                    new_display_date,
+                                    events.clone(),
+                                    RefreshType::Partial,
+                                    Now(Local::now()),
+                                    pos_calculator,
+                                )?;
+                            }
+                            None => renderer.refresh_date(&new_display_date)?,
+                        }
                         PollEvents(
                             st,
                             credentials,
@@ -773,16 +1389,25 @@ pub fn run(
                             time_updated_at,
                             PendingDisplayDate(new_display_date),
                         )
-                    } else if opt_filter(&next_event, short_check) {
-                        let new_display_date = pending_display_date.0 + chrono::Duration::days(1);
-                        renderer.refresh_date(&new_display_date)?;
+                    } else if pending_display_date.0 == display_date
+                        && !prefetching
+                        && !to_prefetch.is_empty()
+                    {
+                        let date = to_prefetch.pop().expect("PollEvents. no date to prefetch");
+                        worker.submit(Job::Day(
+                            credentials.bearer(),
+                            google_ids.clone(),
+                            date,
+                            calendars.google_page_size,
+                        ));
+                        prefetching = true;
                         PollEvents(
                             st,
                             credentials,
                             refreshed_at,
                             started_wait_at,
                             time_updated_at,
-                            PendingDisplayDate(new_display_date),
+                            pending_display_date,
                         )
                     } else {
                         PollEvents(
@@ -797,13 +1422,19 @@ pub fn run(
                 }
             }
             CachedDisplay(st, refresh_token, net_error_at) => {
+                show_overview(renderer, view, &display_date, calendars, cache, &prefetched);
                 let pos_calculator = |_num_event_rows: GlyphYCnt, _screen_height: GlyphYCnt| v_pos;
-                renderer.scroll_events(Now(Local::now()), pos_calculator)?;
+                renderer.display_saved_events(
+                    display_date,
+                    cache.day(&display_date),
+                    Now(Local::now()),
+                    pos_calculator,
+                )?;
                 NetworkOutage(
                     st.into(),
                     refresh_token,
                     net_error_at,
-                    TimeUpdatedAt(*TimeUpdatedAt::now().as_ref() - TIME_UPDATE_PERIOD),
+                    TimeUpdatedAt(*TimeUpdatedAt::now().as_ref() - time_update_period),
                 )
             }
             NetworkOutage(st, refresh_token, net_error_at, time_updated_at) => {
@@ -821,11 +1452,14 @@ pub fn run(
                 if (seconds_since_outage & 8) == 8 {
                     RefreshAuth(st.into(), refresh_token, PendingDisplayDate(display_date))
                 } else {
-                    thread::sleep(BUTTON_POLL_PERIOD);
-                    let reset_event = reset_button.event(&mut gpio)?;
-                    let scroll_event = scroll_button.event(&mut gpio)?;
+                    thread::sleep(button_poll_period);
+                    let reset_event = reset_button.event(gpio)?;
+                    let back_event = back_button.event(gpio)?;
+                    let next_event = next_button.event(gpio)?;
+                    let scroll_event = scroll_button.event(gpio)?;
 
                     let short_check = |e: &LongButtonEvent| e.is_short_press();
+                    let release_check = |e: &LongButtonEvent| e.is_release();
                     let long_check = |e: &LongButtonEvent| e.is_long_press();
 
                     if opt_filter(&reset_event, long_check) {
@@ -835,32 +1469,293 @@ pub fn run(
                         println!("network outage. shutdown event");
                         shutdown()?;
                         NetworkOutage(st, refresh_token, net_error_at, time_updated_at)
+                    } else if opt_filter(&next_event, long_check) {
+                        //the events are shown again once the button is released
+                        view = view.next();
+                        v_pos = GLYPH_Y_ORIGIN;
+                        println!("NetworkOutage. switched to the {:?} view", view);
+                        NetworkOutage(st, refresh_token, net_error_at, time_updated_at)
+                    } else if view == View::Month && opt_filter(&back_event, long_check) {
+                        //the selected day is shown once the button is released
+                        view = View::Day;
+                        v_pos = GLYPH_Y_ORIGIN;
+                        NetworkOutage(st, refresh_token, net_error_at, time_updated_at)
+                    } else if view == View::Month && opt_filter(&scroll_event, short_check) {
+                        display_date = next_in_month(&display_date);
+                        renderer.select_date(display_date, Now(Local::now()))?;
+                        NetworkOutage(st, refresh_token, net_error_at, time_updated_at)
                     } else if opt_filter(&scroll_event, short_check) {
                         println!("NetworkOutage. before scroll. v_pos: {:?}", v_pos);
-                        v_pos = GlyphYCnt(v_pos.0 + V_POS_INC).into();
+                        v_pos = GlyphYCnt(v_pos.0 + scroll_rows).into();
                         let pos_calculator =
                             |num_event_rows: GlyphYCnt, screen_height: GlyphYCnt| {
-                                v_pos = new_pos(v_pos, num_event_rows, screen_height);
+                                v_pos = new_pos(v_pos, num_event_rows, screen_height, scroll_rows);
                                 println!("pos_calculator. v_pos {:?} num_event_rows {:?} screen_height {:?} v_pos {:?}", v_pos, num_event_rows, screen_height, v_pos);
                                 v_pos
                             };
                         renderer.scroll_events(Now(Local::now()), pos_calculator)?;
                         println!("NetworkOutage. after scroll. v_pos: {:?}", v_pos);
                         NetworkOutage(st.into(), refresh_token, net_error_at, time_updated_at)
+                    } else if opt_filter(&back_event, release_check)
+                        || opt_filter(&next_event, release_check)
+                    {
+                        v_pos = GLYPH_Y_ORIGIN;
+                        show_overview(renderer, view, &display_date, calendars, cache, &prefetched);
+                        let pos_calculator =
+                            |num_event_rows: GlyphYCnt, screen_height: GlyphYCnt| {
+                                v_pos = new_pos(v_pos, num_event_rows, screen_height, scroll_rows);
+                                v_pos
+                            };
+                        renderer.display_saved_events(
+                            display_date,
+                            cache.day(&display_date),
+                            Now(Local::now()),
+                            pos_calculator,
+                        )?;
+                        NetworkOutage(st, refresh_token, net_error_at, time_updated_at)
+                    } else if opt_filter(&back_event, short_check) {
+                        display_date = navigate(view, &display_date, false);
+                        renderer.refresh_date(&display_date)?;
+                        NetworkOutage(st, refresh_token, net_error_at, time_updated_at)
+                    } else if opt_filter(&next_event, short_check) {
+                        display_date = navigate(view, &display_date, true);
+                        renderer.refresh_date(&display_date)?;
+                        NetworkOutage(st, refresh_token, net_error_at, time_updated_at)
                     } else {
                         NetworkOutage(st, refresh_token, net_error_at, time_updated_at)
                     }
                 }
             }
+            ReadLocalEvents(st, refresh_type, pending_display_date) => {
+                let new_today = Local::today().and_hms(0, 0, 0);
+                if new_today != today && new_today != pending_display_date.0 {
+                    today = new_today;
+                    ReadLocalEvents(st, refresh_type, PendingDisplayDate(new_today))
+                } else {
+                    today = new_today;
+                    worker.submit(Job::Local(
+                        pending_display_date.0,
+                        refresh_type == RefreshType::Full,
+                    ));
+                    AwaitLocalEvents(
+                        st.into(),
+                        refresh_type,
+                        pending_display_date,
+                        TimeUpdatedAt::now(),
+                    )
+                }
+            }
+            AwaitLocalEvents(st, refresh_type, pending_display_date, time_updated_at) => match worker
+                .reply()
+            {
+                Some(Reply::Local(date, local)) if prefetching => {
+                    prefetching = false;
+                    prefetched.insert(date, local_appointments(local));
+                    AwaitLocalEvents(st, refresh_type, pending_display_date, time_updated_at)
+                }
+                Some(_) if navigated_to.is_some() => {
+                    //the date was changed while reading so the reply is no longer wanted
+                    let new_display_date = navigated_to.take().expect("AwaitLocalEvents. no date");
+                    v_pos = GLYPH_Y_ORIGIN;
+                    ReadLocalEvents(
+                        st.into(),
+                        RefreshType::Partial,
+                        PendingDisplayDate(new_display_date),
+                    )
+                }
+                Some(Reply::Local(_date, local)) => {
+                    display_date = pending_display_date.0;
+                    let events = local_appointments(local);
+                    to_prefetch =
+                        plan_prefetch(view, &display_date, &refresh_type, &mut prefetched);
+                    prefetched.insert(display_date, events.clone());
+                    show_overview(renderer, view, &display_date, calendars, cache, &prefetched);
+                    let pos_calculator = |num_event_rows: GlyphYCnt, screen_height: GlyphYCnt| {
+                        v_pos = new_pos(v_pos, num_event_rows, screen_height, scroll_rows);
+                        v_pos
+                    };
+                    renderer.display_events(
+                        display_date,
+                        events,
+                        refresh_type,
+                        Now(Local::now()),
+                        pos_calculator,
+                    )?;
+                    LocalEvents(
+                        st.into(),
+                        DownloadedAt::now(),
+                        TimeUpdatedAt::now(),
+                        pending_display_date,
+                    )
+                }
+                Some(_) => {
+                    error!("AwaitLocalEvents. unexpected reply from network worker");
+                    AwaitLocalEvents(st, refresh_type, pending_display_date, time_updated_at)
+                }
+                None => {
+                    thread::sleep(button_poll_period);
+                    let reset_event = reset_button.event(gpio)?;
+                    let back_event = back_button.event(gpio)?;
+                    let next_event = next_button.event(gpio)?;
+                    let scroll_event = scroll_button.event(gpio)?;
+
+                    let short_check = |e: &LongButtonEvent| e.is_short_press();
+
+                    let mut time_updated_at = time_updated_at;
+                    if opt_filter(&reset_event, short_check) {
+                        shutdown()?;
+                    } else if opt_filter(&scroll_event, short_check) {
+                        v_pos = GlyphYCnt(v_pos.0 + scroll_rows);
+                        let pos_calculator =
+                            |num_event_rows: GlyphYCnt, screen_height: GlyphYCnt| {
+                                v_pos = new_pos(v_pos, num_event_rows, screen_height, scroll_rows);
+                                v_pos
+                            };
+                        renderer.scroll_events(Now(Local::now()), pos_calculator)?;
+                    } else if opt_filter(&back_event, short_check)
+                        || opt_filter(&next_event, short_check)
+                    {
+                        let from = navigated_to.unwrap_or(pending_display_date.0);
+                        let new_display_date = if opt_filter(&back_event, short_check) {
+                            navigate(view, &from, false)
+                        } else {
+                            navigate(view, &from, true)
+                        };
+                        renderer.refresh_date(&new_display_date)?;
+                        navigated_to = Some(new_display_date);
+                    } else if time_updated_at.as_ref().elapsed() >= time_update_period {
+                        let pos_calculator =
+                            |_num_event_rows: GlyphYCnt, _screen_height: GlyphYCnt| v_pos;
+                        renderer.scroll_events(Now(Local::now()), pos_calculator)?;
+                        time_updated_at = TimeUpdatedAt::now();
+                    }
+                    AwaitLocalEvents(st, refresh_type, pending_display_date, time_updated_at)
+                }
+            },
+            LocalEvents(st, read_at, time_updated_at, pending_display_date) => {
+                match worker.reply() {
+                    Some(Reply::Local(date, local)) => {
+                        prefetching = false;
+                        prefetched.insert(date, local_appointments(local));
+                        if view != View::Day && pending_display_date.0 == display_date {
+                            show_overview(
+                                renderer,
+                                view,
+                                &display_date,
+                                calendars,
+                                cache,
+                                &prefetched,
+                            );
+                            let pos_calculator =
+                                |_num_event_rows: GlyphYCnt, _screen_height: GlyphYCnt| v_pos;
+                            renderer.scroll_events(Now(Local::now()), pos_calculator)?;
+                        }
+                    }
+                    Some(_) => error!("LocalEvents. unexpected reply from network worker"),
+                    None => (),
+                }
+
+                let waiting_for = read_at.as_ref().elapsed();
+                let same_time_for = time_updated_at.as_ref().elapsed();
+                renderer.display_status(Status::AllOk, (waiting_for.as_secs() & 2) == 2)?;
+
+                thread::sleep(button_poll_period);
+                let reset_event = reset_button.event(gpio)?;
+                let back_event = back_button.event(gpio)?;
+                let next_event = next_button.event(gpio)?;
+                let scroll_event = scroll_button.event(gpio)?;
+
+                let short_check = |e: &LongButtonEvent| e.is_short_press();
+                let release_check = |e: &LongButtonEvent| e.is_release();
+                let long_check = |e: &LongButtonEvent| e.is_long_press();
+
+                if opt_filter(&reset_event, short_check) {
+                    shutdown()?;
+                    LocalEvents(st, read_at, time_updated_at, pending_display_date)
+                } else if opt_filter(&next_event, long_check) {
+                    //the events are read again once the button is released
+                    view = view.next();
+                    v_pos = GLYPH_Y_ORIGIN;
+                    LocalEvents(st, read_at, time_updated_at, pending_display_date)
+                } else if view == View::Month && opt_filter(&back_event, long_check) {
+                    //the selected day is read once the button is released
+                    view = View::Day;
+                    v_pos = GLYPH_Y_ORIGIN;
+                    LocalEvents(st, read_at, time_updated_at, pending_display_date)
+                } else if opt_filter(&scroll_event, long_check) {
+                    ReadLocalEvents(
+                        st.into(),
+                        RefreshType::Full,
+                        PendingDisplayDate(Local::today().and_hms(0, 0, 0)),
+                    )
+                } else if view == View::Month && opt_filter(&scroll_event, short_check) {
+                    display_date = next_in_month(&pending_display_date.0);
+                    renderer.select_date(display_date, Now(Local::now()))?;
+                    LocalEvents(st, read_at, time_updated_at, PendingDisplayDate(display_date))
+                } else if opt_filter(&scroll_event, short_check) {
+                    v_pos = GlyphYCnt(v_pos.0 + scroll_rows);
+                    let pos_calculator = |num_event_rows: GlyphYCnt, screen_height: GlyphYCnt| {
+                        v_pos = new_pos(v_pos, num_event_rows, screen_height, scroll_rows);
+                        v_pos
+                    };
+                    renderer.scroll_events(Now(Local::now()), pos_calculator)?;
+                    LocalEvents(st, read_at, time_updated_at, pending_display_date)
+                } else if waiting_for >= recheck_period {
+                    ReadLocalEvents(st.into(), RefreshType::Full, pending_display_date)
+                } else if same_time_for >= time_update_period {
+                    let pos_calculator =
+                        |_num_event_rows: GlyphYCnt, _screen_height: GlyphYCnt| v_pos;
+                    renderer.scroll_events(Now(Local::now()), pos_calculator)?;
+                    LocalEvents(st, read_at, TimeUpdatedAt::now(), pending_display_date)
+                } else if opt_filter(&back_event, release_check)
+                    || opt_filter(&next_event, release_check)
+                {
+                    v_pos = GLYPH_Y_ORIGIN;
+                    ReadLocalEvents(st.into(), RefreshType::Partial, pending_display_date)
+                } else if opt_filter(&back_event, short_check) {
+                    let new_display_date = navigate(view, &pending_display_date.0, false);
+                    renderer.refresh_date(&new_display_date)?;
+                    LocalEvents(
+                        st,
+                        read_at,
+                        time_updated_at,
+                        PendingDisplayDate(new_display_date),
+                    )
+                } else if opt_filter(&next_event, short_check) {
+                    let new_display_date = navigate(view, &pending_display_date.0, true);
+                    renderer.refresh_date(&new_display_date)?;
+                    LocalEvents(
+                        st,
+                        read_at,
+                        time_updated_at,
+                        PendingDisplayDate(new_display_date),
+                    )
+                } else if pending_display_date.0 == display_date
+                    && !prefetching
+                    && !to_prefetch.is_empty()
+                {
+                    let date = to_prefetch.pop().expect("LocalEvents. no date to prefetch");
+                    worker.submit(Job::Local(date, false));
+                    prefetching = true;
+                    LocalEvents(st, read_at, time_updated_at, pending_display_date)
+                } else {
+                    LocalEvents(st, read_at, time_updated_at, pending_display_date)
+                }
+            }
             ErrorWait(st, started_wait_at) => {
                 let waiting_for = started_wait_at.as_ref().elapsed();
-                if waiting_for >= RECHECK_PERIOD {
+                if waiting_for >= recheck_period {
                     LoadAuth(st.into())
                 } else {
-                    thread::sleep(BUTTON_POLL_PERIOD);
-                    let reset_event = reset_button.event(&mut gpio)?;
+                    thread::sleep(button_poll_period);
+                    let reset_event = reset_button.event(gpio)?;
                     if opt_filter(&reset_event, |e| e.is_long_press()) {
-                        RequestCodes(st.into())
+                        if client.is_some() {
+                            RequestCodes(st.into())
+                        } else {
+                            //shows why the client couldn't be loaded again
+                            LoadAuth(st.into())
+                        }
                     } else if opt_filter(&reset_event, |e| e.is_short_press()) {
                         shutdown()?;
                         ErrorWait(st, started_wait_at)
@@ -871,9 +1766,535 @@ pub fn run(
             }
             DisplayError(st, message) => {
                 eprintln!("Error: {}", message);
+                renderer.display_error(&message)?;
                 ErrorWait(st.into(), DownloadedAt::now())
             }
         };
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cal_display::{
+            tests::{shown, Recorder},
+            RefreshType, View,
+        },
+        cal_machine::{
+            add_months,
+            cache::EventCache,
+            days_to_prefetch,
+            evs::{AppsReadonly, EndDate, Event, StartDate},
+            mock_google::{Canned, MockGoogle, DEVICE_CODE_PATH, PRIMARY_EVENTS_PATH, TOKEN_PATH},
+            retriever::{
+                ACCOUNTS_URL_VAR, API_URL_VAR, CLIENT_ID_VAR, CLIENT_SECRET_FILE, CLIENT_SECRET_VAR,
+            },
+            next_in_month, plan_prefetch, load_settings, run,
+            sources::{Calendars, CalendarsConfig, Error as SourceError, EventSource},
+            Persisted, RefreshToken, Signals, REFRESH_TOKEN_FILE,
+        },
+        gpio_in::{Disconnected, Error as GPIOError, Pin, Pins},
+        settings::{Settings, SETTINGS_FILE},
+        temp_dir::TempDir,
+    };
+    use chrono::prelude::*;
+    use std::{
+        collections::BTreeMap,
+        env, fs,
+        path::Path,
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            mpsc::{self, Receiver},
+            Arc, Mutex,
+        },
+        thread,
+        time::{Duration, Instant},
+    };
+
+    const TIMEOUT: Duration = Duration::from_secs(20);
+    const CLIENT_SECRET: &str =
+        r#"{"installed": {"client_id": "mirror.apps.googleusercontent.com", "client_secret": "secret"}}"#;
+    const GRACE: Duration = Duration::from_millis(300);
+
+    //the endpoints are read from the environment, which is shared by all tests
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    /// A calendar whose first read returns at once and whose later ones
+    /// stall until the sender of release is dropped.
+    struct Stalling {
+        release: Receiver<()>,
+        stalled: Arc<AtomicBool>,
+        reads: Arc<AtomicUsize>,
+    }
+
+    impl EventSource for Stalling {
+        fn read(
+            &mut self,
+            _min_time: &DateTime<Local>,
+            _max_time: &DateTime<Local>,
+        ) -> Result<Vec<Event>, SourceError> {
+            if self.reads.load(Ordering::SeqCst) > 0 {
+                self.stalled.store(true, Ordering::SeqCst);
+                let _ = self.release.recv();
+            }
+            self.reads.fetch_add(1, Ordering::SeqCst);
+            Ok(Vec::new())
+        }
+    }
+
+    /// A calendar with the same event every morning.
+    struct Daily(&'static str);
+
+    impl EventSource for Daily {
+        fn read(
+            &mut self,
+            min_time: &DateTime<Local>,
+            _max_time: &DateTime<Local>,
+        ) -> Result<Vec<Event>, SourceError> {
+            Ok(vec![Event {
+                summary: self.0.to_string(),
+                description: None,
+                all_consuming: true,
+                start: StartDate(*min_time + chrono::Duration::hours(9)),
+                end: EndDate(*min_time + chrono::Duration::hours(10)),
+                tag: None,
+            }])
+        }
+    }
+
+    /// Presses the button on pin once, for hold, as soon as when is set.
+    struct PressWhen {
+        pin: Pin,
+        hold: Duration,
+        when: Arc<AtomicBool>,
+        since: Instant,
+        pressed_at: Option<Instant>,
+    }
+
+    impl PressWhen {
+        fn new(pin: usize, hold: Duration, when: Arc<AtomicBool>) -> PressWhen {
+            PressWhen {
+                pin: Pin(pin),
+                hold,
+                when,
+                since: Instant::now(),
+                pressed_at: None,
+            }
+        }
+    }
+
+    impl Pins for PressWhen {
+        fn pinin(&mut self, gpio: Pin) -> Result<(bool, Duration), GPIOError> {
+            if gpio != self.pin {
+                return Ok((false, self.since.elapsed()));
+            }
+            if self.pressed_at.is_none() && self.when.load(Ordering::SeqCst) {
+                self.pressed_at = Some(Instant::now());
+            }
+            match self.pressed_at {
+                Some(pressed_at) if pressed_at.elapsed() < self.hold => {
+                    Ok((true, pressed_at.elapsed()))
+                }
+                Some(pressed_at) => Ok((false, pressed_at.elapsed() - self.hold)),
+                None => Ok((false, self.since.elapsed())),
+            }
+        }
+    }
+
+    struct Outcome {
+        texts: Vec<String>,
+        requests: Vec<String>,
+        saved_token: Option<String>,
+    }
+
+    impl Outcome {
+        fn shown(&self, text: &str) -> bool {
+            shown(&self.texts, text)
+        }
+
+        fn requested(&self, path: &str) -> usize {
+            self.requests
+                .iter()
+                .filter(|request| request.split('?').next().unwrap_or_default().ends_with(path))
+                .count()
+        }
+    }
+
+    fn today() -> String {
+        Local::today().format("%Y-%m-%d").to_string()
+    }
+
+    /// Points the Google services at server, and takes the client
+    /// credentials only from the var dir.
+    fn use_mock(server: &MockGoogle) {
+        env::set_var(ACCOUNTS_URL_VAR, server.url());
+        env::set_var(API_URL_VAR, server.url());
+        env::remove_var(CLIENT_ID_VAR);
+        env::remove_var(CLIENT_SECRET_VAR);
+    }
+
+    /// Runs the state machine against server until done is true of the
+    /// texts displayed and the requests made, and a little longer.
+    fn drive(
+        name: &str,
+        server: &MockGoogle,
+        client_secret: Option<&str>,
+        refresh_token: Option<&str>,
+        done: impl Fn(&[String], &[String]) -> bool + Send + 'static,
+    ) -> Outcome {
+        let _env = ENV_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        use_mock(server);
+
+        let dir = TempDir::new(&format!("run_{}", name));
+        if let Some(client_secret) = client_secret {
+            fs::write(dir.join(CLIENT_SECRET_FILE), client_secret).expect("client secret file");
+        }
+        if let Some(token) = refresh_token {
+            RefreshToken(token.to_string())
+                .save(&dir.join(REFRESH_TOKEN_FILE))
+                .expect("token file");
+        }
+
+        let (texts, mut renderer) = Recorder::renderer();
+        let requests = server.requests();
+        let quitter = Arc::new(AtomicBool::new(false));
+        let reload = AtomicBool::new(false);
+        let watched = (texts.clone(), requests.clone(), quitter.clone());
+        let watcher = thread::spawn(move || {
+            let (texts, requests, quitter) = watched;
+            let started = Instant::now();
+            loop {
+                let finished = done(
+                    &texts.lock().expect("texts"),
+                    &requests.lock().expect("requests"),
+                );
+                if finished || started.elapsed() >= TIMEOUT {
+                    thread::sleep(GRACE);
+                    quitter.store(true, Ordering::SeqCst);
+                    return finished;
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+        });
+
+        let mut calendars = CalendarsConfig::default().calendars();
+        let mut cache = EventCache::load(&dir.join("cache.json"), &dir.join("snapshot.json"));
+        let mut settings = Settings::load(&dir).expect("settings");
+        let saved_token = Mutex::new(None);
+        run(
+            &mut renderer,
+            &mut Disconnected::new(),
+            &Signals {
+                quit: &quitter,
+                reload: &reload,
+            },
+            &mut settings,
+            &mut calendars,
+            &mut cache,
+            |persisted, _renderer| {
+                if let Persisted::RefreshToken(RefreshToken(token)) = persisted {
+                    *saved_token.lock().expect("saved token") = Some(token.clone());
+                }
+                Ok(())
+            },
+        )
+        .expect("run");
+        assert!(watcher.join().expect("watcher"), "timed out");
+
+        let texts = texts.lock().expect("texts").clone();
+        let requests = requests.lock().expect("requests").clone();
+        Outcome {
+            texts,
+            requests,
+            saved_token: saved_token.into_inner().expect("saved token"),
+        }
+    }
+
+    #[test]
+    fn device_authorisation_and_paging() {
+        let server = MockGoogle::start(vec![
+            (DEVICE_CODE_PATH, vec![Canned::device_code("ABCD-EFGH")]),
+            (
+                TOKEN_PATH,
+                vec![
+                    Canned::poll_error(428, "authorization_pending"),
+                    Canned::poll_error(429, "slow_down"),
+                    Canned::tokens("refresh-1"),
+                ],
+            ),
+            (
+                PRIMARY_EVENTS_PATH,
+                vec![
+                    Canned::events(&today(), &["Standup"], Some("page-2")),
+                    Canned::events(&today(), &["Retro"], None),
+                ],
+            ),
+        ]);
+        let outcome = drive("paging", &server, Some(CLIENT_SECRET), None, |texts, _| {
+            shown(texts, "Retro")
+        });
+
+        assert!(outcome.shown("ABCD-EFGH"));
+        assert!(outcome.shown("Standup"));
+        assert_eq!(outcome.requested(TOKEN_PATH), 3);
+        assert!(outcome.requests.iter().any(|request| request.contains("pageToken=page-2")));
+        assert_eq!(outcome.saved_token, Some("refresh-1".to_string()));
+    }
+
+    #[test]
+    fn access_denied() {
+        let server = MockGoogle::start(vec![
+            (DEVICE_CODE_PATH, vec![Canned::device_code("ABCD-EFGH")]),
+            (TOKEN_PATH, vec![Canned::poll_error(403, "access_denied")]),
+        ]);
+        let outcome = drive("denied", &server, Some(CLIENT_SECRET), None, |texts, _| {
+            shown(texts, "refused")
+        });
+
+        assert!(outcome.shown("Error"));
+        assert_eq!(outcome.requested(TOKEN_PATH), 1);
+        assert_eq!(outcome.requested(PRIMARY_EVENTS_PATH), 0);
+        assert_eq!(outcome.saved_token, None);
+    }
+
+    #[test]
+    fn refresh_unauthorised() {
+        let server = MockGoogle::start(vec![
+            (TOKEN_PATH, vec![Canned::new(401, "{}")]),
+            (DEVICE_CODE_PATH, vec![Canned::device_code("WXYZ-WXYZ")]),
+        ]);
+        let outcome = drive("unauthorised", &server, Some(CLIENT_SECRET), Some("stale"), |texts, _| {
+            shown(texts, "WXYZ-WXYZ")
+        });
+
+        assert_eq!(outcome.requests[0], format!("POST {}", TOKEN_PATH));
+        assert_eq!(outcome.requested(DEVICE_CODE_PATH), 1);
+    }
+
+    #[test]
+    fn server_error() {
+        let server = MockGoogle::start(vec![
+            (TOKEN_PATH, vec![Canned::refreshed()]),
+            (PRIMARY_EVENTS_PATH, vec![Canned::new(503, "{}")]),
+        ]);
+        let outcome = drive(
+            "server_error",
+            &server,
+            Some(CLIENT_SECRET),
+            Some("refresh-1"),
+            |texts, _| shown(texts, "503"),
+        );
+
+        assert_eq!(outcome.requested(PRIMARY_EVENTS_PATH), 1);
+        assert!(!outcome.shown("Offline"));
+        assert_eq!(outcome.saved_token, None);
+    }
+
+    #[test]
+    fn missing_client_credentials() {
+        let server = MockGoogle::start(vec![]);
+        let outcome = drive("no_client", &server, None, None, |texts, _| {
+            shown(texts, CLIENT_SECRET_FILE)
+        });
+
+        assert!(outcome.shown("Error"));
+        assert!(outcome.requests.is_empty());
+
+        let empty = r#"{"installed": {"client_id": "", "client_secret": ""}}"#;
+        let outcome = drive("empty_client", &server, Some(empty), None, |texts, _| {
+            shown(texts, "empty")
+        });
+        assert!(outcome.requests.is_empty());
+    }
+
+    /// Runs the state machine on the settings in dir with pins until
+    /// done is true of the texts displayed, and returns them.
+    fn drive_pins(
+        dir: &Path,
+        calendars: &mut Calendars,
+        pins: &mut dyn Pins,
+        done: impl Fn(&[String]) -> bool + Send + 'static,
+    ) -> Vec<String> {
+        let _env = ENV_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        //nothing is scripted so any request to Google isn't found
+        let server = MockGoogle::start(Vec::new());
+        use_mock(&server);
+        let (texts, mut renderer) = Recorder::renderer();
+        let quitter = Arc::new(AtomicBool::new(false));
+        let watched = (texts.clone(), quitter.clone());
+        let watcher = thread::spawn(move || {
+            let (texts, quitter) = watched;
+            let started = Instant::now();
+            loop {
+                let finished = done(&texts.lock().expect("texts"));
+                if finished || started.elapsed() >= TIMEOUT {
+                    quitter.store(true, Ordering::SeqCst);
+                    return finished;
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+        });
+
+        let mut settings = Settings::load(dir).expect("settings");
+        let mut cache = EventCache::load(&dir.join("cache.json"), &dir.join("snapshot.json"));
+        run(
+            &mut renderer,
+            pins,
+            &Signals {
+                quit: &quitter,
+                reload: &AtomicBool::new(false),
+            },
+            &mut settings,
+            calendars,
+            &mut cache,
+            |_persisted, _renderer| Ok(()),
+        )
+        .expect("run");
+        assert!(watcher.join().expect("watcher"), "timed out");
+
+        let texts = texts.lock().expect("texts").clone();
+        texts
+    }
+
+    #[test]
+    fn buttons_answered_while_reading() {
+        let dir = TempDir::new("stalled");
+        let mut calendars = CalendarsConfig {
+            google: false,
+            ..CalendarsConfig::default()
+        }
+        .calendars();
+        let (release, stalling) = mpsc::channel();
+        let stalled = Arc::new(AtomicBool::new(false));
+        let reads = Arc::new(AtomicUsize::new(0));
+        calendars.sources.others.push((
+            "stalling".to_string(),
+            Box::new(Stalling {
+                release: stalling,
+                stalled: stalled.clone(),
+                reads: reads.clone(),
+            }),
+        ));
+        let next = Settings::default().buttons.next;
+        let mut pins = PressWhen::new(next, Duration::from_millis(100), stalled);
+
+        //the day after is read ahead, which stalls, before next is pressed
+        let tomorrow = (Local::today() + chrono::Duration::days(1)).format("%e %b").to_string();
+        let shown_tomorrow = tomorrow.clone();
+        let texts = drive_pins(&dir, &mut calendars, &mut pins, move |texts| {
+            shown(texts, &shown_tomorrow)
+        });
+
+        assert!(shown(&texts, &tomorrow));
+        assert_eq!(reads.load(Ordering::SeqCst), 1);
+        drop(release);
+    }
+
+    #[test]
+    fn reset_without_client_credentials() {
+        let dir = TempDir::new("reset");
+        let timing = "[timing]\nlong_press_millis = 100\nrelease_millis = 50\n";
+        fs::write(dir.join(SETTINGS_FILE), timing).expect("settings file");
+        let errored = Arc::new(AtomicBool::new(false));
+        let reset = Settings::default().buttons.reset;
+        let mut pins = PressWhen::new(reset, Duration::from_millis(300), errored.clone());
+
+        let mut calendars = CalendarsConfig::default().calendars();
+        let texts = drive_pins(&dir, &mut calendars, &mut pins, move |texts| {
+            let errors = texts.iter().filter(|text| text.contains(CLIENT_SECRET_FILE)).count();
+            errored.store(errors > 0, Ordering::SeqCst);
+            errors > 1
+        });
+
+        assert!(texts.iter().filter(|text| text.contains(CLIENT_SECRET_FILE)).count() > 1);
+    }
+
+    #[test]
+    fn month_view_opens_the_selected_day() {
+        let dir = TempDir::new("drill");
+        let toml = "view = \"month\"\n[timing]\nlong_press_millis = 100\nrelease_millis = 50\n";
+        fs::write(dir.join(SETTINGS_FILE), toml).expect("settings file");
+        let mut calendars = CalendarsConfig {
+            google: false,
+            ..CalendarsConfig::default()
+        }
+        .calendars();
+        calendars.sources.others.push(("daily".to_string(), Box::new(Daily("Dentist"))));
+        let month_shown = Arc::new(AtomicBool::new(false));
+        let back = Settings::default().buttons.back;
+        let mut pins = PressWhen::new(back, Duration::from_millis(300), month_shown.clone());
+
+        let texts = drive_pins(&dir, &mut calendars, &mut pins, move |texts| {
+            month_shown.store(shown(texts, "Mo Tu We"), Ordering::SeqCst);
+            shown(texts, "Dentist")
+        });
+
+        let month = texts.iter().position(|text| text.contains("Mo Tu We"));
+        let day = texts.iter().position(|text| text.contains("09:00-10:00 Dentist"));
+        assert!(month.expect("month shown") < day.expect("day shown"));
+    }
+
+    #[test]
+    fn reload_keeps_settings_until_valid() {
+        let dir = TempDir::new("reload");
+
+        fs::write(dir.join(SETTINGS_FILE), "[timing]\nrecheck_secs = 0\n").expect("settings file");
+        let message = load_settings(&dir).err().expect("invalid settings");
+        assert!(message.contains(SETTINGS_FILE));
+
+        fs::write(dir.join(SETTINGS_FILE), "[timing]\nrecheck_secs = 600\n").expect("settings file");
+        let (reloaded, _calendars) = load_settings(&dir).expect("valid settings");
+        assert_eq!(reloaded.timing.recheck_secs, 600);
+        assert_eq!(reloaded.var_dir, *dir);
+    }
+
+    #[test]
+    fn week_view_reads_the_week_ahead() {
+        let today = Local::today().and_hms(0, 0, 0);
+        let mut days = days_to_prefetch(View::Week, &today);
+        assert_eq!(days.len(), 7);
+        assert_eq!(days.pop(), Some(today + chrono::Duration::days(1)));
+        assert_eq!(days.first(), Some(&(today - chrono::Duration::days(1))));
+        assert_eq!(days_to_prefetch(View::Day, &today).len(), 2);
+    }
+
+    #[test]
+    fn month_navigation() {
+        let day = |year, month, day| Local.ymd(year, month, day).and_hms(0, 0, 0);
+        assert_eq!(add_months(&day(2026, 1, 31), 1), day(2026, 2, 28));
+        assert_eq!(add_months(&day(2026, 1, 15), -1), day(2025, 12, 15));
+        assert_eq!(add_months(&day(2026, 12, 15), 1), day(2027, 1, 15));
+        assert_eq!(next_in_month(&day(2026, 10, 30)), day(2026, 10, 31));
+        assert_eq!(next_in_month(&day(2026, 10, 31)), day(2026, 10, 1));
+    }
+
+    #[test]
+    fn month_days_kept_while_navigating() {
+        let day = |month, day| Local.ymd(2026, month, day).and_hms(0, 0, 0);
+        let mut prefetched = BTreeMap::new();
+        let october = plan_prefetch(View::Month, &day(10, 2), &RefreshType::Full, &mut prefetched);
+        assert_eq!(october.len(), 30);
+        for date in october {
+            prefetched.insert(date, AppsReadonly::saved(None, Vec::new()));
+        }
+
+        let november =
+            plan_prefetch(View::Month, &day(11, 2), &RefreshType::Partial, &mut prefetched);
+        assert_eq!(november.len(), 29);
+        for date in november {
+            prefetched.insert(date, AppsReadonly::saved(None, Vec::new()));
+        }
+        assert!(plan_prefetch(View::Month, &day(10, 2), &RefreshType::Partial, &mut prefetched)
+            .is_empty());
+        assert_eq!(
+            plan_prefetch(View::Month, &day(10, 2), &RefreshType::Full, &mut prefetched).len(),
+            30
+        );
+
+        plan_prefetch(View::Month, &day(12, 2), &RefreshType::Partial, &mut prefetched);
+        assert!(prefetched.contains_key(&day(11, 30)));
+        assert!(!prefetched.contains_key(&day(10, 31)));
+    }
+
+}
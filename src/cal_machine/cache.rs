@@ -0,0 +1,217 @@
+/*
+Copyright [2019] [Kieran White]
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::cal_machine::evs::{AppsReadonly, Email, EndDate, Event, StartDate};
+use chrono::{prelude::*, Duration};
+use log::{error, trace};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration as StdDuration, Instant},
+};
+
+pub const DEFAULT_CACHE_DIR: &str = "/run/calendar_mirror";
+pub const CACHE_FILE: &str = "events.json";
+
+const DAYS_BEFORE: i64 = 1;
+const DAYS_AFTER: i64 = 14;
+const SNAPSHOT_PERIOD: StdDuration = StdDuration::from_secs(6 * 60 * 60);
+
+#[derive(Serialize, Deserialize, Debug)]
+struct CachedEvent {
+    summary: String,
+    description: Option<String>,
+    all_consuming: bool,
+    start: DateTime<Local>,
+    end: DateTime<Local>,
+    tag: Option<char>,
+}
+
+impl From<&Event> for CachedEvent {
+    fn from(ev: &Event) -> CachedEvent {
+        CachedEvent {
+            summary: ev.summary.clone(),
+            description: ev.description.clone(),
+            all_consuming: ev.all_consuming,
+            start: *ev.start.as_ref(),
+            end: *ev.end.as_ref(),
+            tag: ev.tag,
+        }
+    }
+}
+
+impl From<&CachedEvent> for Event {
+    fn from(ev: &CachedEvent) -> Event {
+        Event {
+            summary: ev.summary.clone(),
+            description: ev.description.clone(),
+            all_consuming: ev.all_consuming,
+            start: StartDate(ev.start),
+            end: EndDate(ev.end),
+            tag: ev.tag,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct CachedDay {
+    downloaded: DateTime<Local>,
+    email: Option<String>,
+    events: Vec<CachedEvent>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct CachedDays(BTreeMap<NaiveDate, CachedDay>);
+
+/// Recently downloaded days of events, so that they can be shown
+/// when the network is down, even straight after booting. The cache
+/// is rewritten to a file on a tmpfs whenever it changes and is only
+/// occasionally snapshotted to the configuration partition, which has
+/// to be remounted to do so.
+pub struct EventCache {
+    days: CachedDays,
+    file: PathBuf,
+    snapshotted_at: Instant,
+    changed: bool,
+}
+
+impl EventCache {
+    fn read(path: &Path) -> io::Result<CachedDays> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// Loads the cache from its file, falling back to the snapshot after
+    /// a reboot has emptied the tmpfs.
+    pub fn load(file: &Path, snapshot: &Path) -> EventCache {
+        let days = EventCache::read(file)
+            .or_else(|_| EventCache::read(snapshot))
+            .unwrap_or_else(|err| {
+                trace!("no event cache loaded: {:?}", err);
+                CachedDays::default()
+            });
+        EventCache {
+            days,
+            file: file.to_path_buf(),
+            snapshotted_at: Instant::now(),
+            changed: false,
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut writer, &self.days)?;
+        writer.flush()
+    }
+
+    /// Saves the appointments shown for date, dropping days that are
+    /// no longer close enough to today to be worth keeping.
+    pub fn store(&mut self, date: &DateTime<Local>, apps: &AppsReadonly) {
+        let today = Local::today().naive_local();
+        let (first, last) = (
+            today - Duration::days(DAYS_BEFORE),
+            today + Duration::days(DAYS_AFTER),
+        );
+        let day = date.naive_local().date();
+        self.days.0.retain(|cached, _| *cached >= first && *cached <= last);
+        if day >= first && day <= last {
+            self.days.0.insert(
+                day,
+                CachedDay {
+                    downloaded: Local::now(),
+                    email: apps.email().map(|Email(email)| email),
+                    events: apps.events().iter().map(CachedEvent::from).collect(),
+                },
+            );
+        }
+
+        self.changed = true;
+        if let Err(err) = self.save(&self.file) {
+            error!("failed to write event cache {:?}: {:?}", self.file, err);
+        }
+    }
+
+    /// The saved appointments for date and when they were downloaded.
+    pub fn day(&self, date: &DateTime<Local>) -> Option<(AppsReadonly, DateTime<Local>)> {
+        self.days.0.get(&date.naive_local().date()).map(|day| {
+            (
+                AppsReadonly::saved(
+                    day.email.clone().map(Email),
+                    day.events.iter().map(Event::from).collect(),
+                ),
+                day.downloaded,
+            )
+        })
+    }
+
+    pub fn snapshot_due(&self) -> bool {
+        self.changed && self.snapshotted_at.elapsed() >= SNAPSHOT_PERIOD
+    }
+
+    pub fn snapshotted(&mut self) {
+        self.changed = false;
+        self.snapshotted_at = Instant::now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cal_machine::{
+            cache::EventCache,
+            evs::{AppsReadonly, Email, EndDate, Event, StartDate},
+        },
+        temp_dir::TempDir,
+    };
+    use chrono::{prelude::*, Duration};
+    use std::fs;
+
+    #[test]
+    fn round_trip() {
+        let dir = TempDir::new("cache");
+        let file = dir.join("tmpfs").join("events.json");
+        let snapshot = dir.join("events.json");
+        let today = Local::today().and_hms(0, 0, 0);
+        let event = Event {
+            summary: "Dentist".to_string(),
+            description: None,
+            all_consuming: true,
+            start: StartDate(today + Duration::hours(9)),
+            end: EndDate(today + Duration::hours(10)),
+            tag: Some('H'),
+        };
+
+        let mut cache = EventCache::load(&file, &snapshot);
+        assert!(cache.day(&today).is_none());
+        let apps = AppsReadonly::saved(Some(Email("me@example.com".to_string())), vec![event.clone()]);
+        cache.store(&today, &apps);
+        cache.store(&(today - Duration::days(30)), &apps);
+        cache.save(&snapshot).expect("snapshot");
+
+        fs::remove_file(&file).expect("remove tmpfs copy");
+        let reloaded = EventCache::load(&file, &snapshot);
+        let (apps, _downloaded) = reloaded.day(&today).expect("cached day");
+        assert_eq!(apps.events(), vec![event]);
+        assert_eq!(apps.email(), Some(Email("me@example.com".to_string())));
+        assert!(reloaded.day(&(today - Duration::days(30))).is_none());
+    }
+}
@@ -0,0 +1,236 @@
+/*
+Copyright [2019] [Kieran White]
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::{
+    cal_machine::{evs::Event, ical},
+    err,
+};
+use chrono::prelude::*;
+use log::trace;
+use reqwest::{self, Client, Method, StatusCode};
+
+const REPORT_METHOD: &[u8] = b"REPORT";
+const DEPTH_HEADER: &str = "Depth";
+const CONTENT_TYPE_HEADER: &str = "Content-Type";
+const CONTENT_TYPE_XML: &str = "application/xml; charset=utf-8";
+const CALENDAR_DATA_TAG: &str = "calendar-data";
+const CDATA_START: &str = "<![CDATA[";
+const CDATA_END: &str = "]]>";
+const TIME_RANGE_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+#[derive(Debug)]
+pub struct StatusError(pub StatusCode);
+
+err!(Error {
+    Reqwest(reqwest::Error),
+    Status(StatusError)
+});
+
+impl Error {
+    pub fn message(&self) -> String {
+        match self {
+            Error::Reqwest(err) => format!("HTTP error: {}", err),
+            Error::Status(StatusError(status)) => format!("HTTP status: {}", status),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Credentials {
+    Anonymous,
+    Basic(String, Option<String>),
+    Bearer(String),
+}
+
+/// Reads events from a single CalDAV calendar collection, such as
+/// those served by Nextcloud or Radicale. The server expands recurring
+/// events so that only the instances within the requested period are
+/// returned.
+pub struct CalDav {
+    client: Client,
+    url: String,
+    credentials: Credentials,
+}
+
+impl CalDav {
+    pub fn new(url: &str, credentials: Credentials) -> CalDav {
+        CalDav {
+            client: Client::new(),
+            url: url.to_string(),
+            credentials,
+        }
+    }
+
+    pub fn read(
+        &self,
+        min_time: &DateTime<Local>,
+        max_time: &DateTime<Local>,
+    ) -> Result<Vec<Event>, Error> {
+        let request = self
+            .client
+            .request(
+                Method::from_bytes(REPORT_METHOD).expect("invalid REPORT method"),
+                &self.url,
+            )
+            .header(DEPTH_HEADER, "1")
+            .header(CONTENT_TYPE_HEADER, CONTENT_TYPE_XML)
+            .body(calendar_query(min_time, max_time));
+        let request = match &self.credentials {
+            Credentials::Anonymous => request,
+            Credentials::Basic(username, password) => request.basic_auth(username, password.as_ref()),
+            Credentials::Bearer(token) => request.bearer_auth(token),
+        };
+        trace!("caldav request: {:?}", request);
+
+        let mut resp = request.send()?;
+        match resp.status() {
+            StatusCode::MULTI_STATUS => Ok(calendar_data(&resp.text()?)
+                .iter()
+                .flat_map(|cal| ical::events(cal))
+                .collect()),
+            other => Err(StatusError(other).into()),
+        }
+    }
+}
+
+fn calendar_query(min_time: &DateTime<Local>, max_time: &DateTime<Local>) -> String {
+    let start = min_time.with_timezone(&Utc).format(TIME_RANGE_FORMAT);
+    let end = max_time.with_timezone(&Utc).format(TIME_RANGE_FORMAT);
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8" ?>
+<C:calendar-query xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+  <D:prop>
+    <C:calendar-data>
+      <C:expand start="{start}" end="{end}"/>
+    </C:calendar-data>
+  </D:prop>
+  <C:filter>
+    <C:comp-filter name="VCALENDAR">
+      <C:comp-filter name="VEVENT">
+        <C:time-range start="{start}" end="{end}"/>
+      </C:comp-filter>
+    </C:comp-filter>
+  </C:filter>
+</C:calendar-query>
+"#,
+        start = start,
+        end = end
+    )
+}
+
+fn unescape_xml(text: &str) -> String {
+    let text = text.trim();
+    if text.starts_with(CDATA_START) && text.ends_with(CDATA_END) {
+        return text[CDATA_START.len()..text.len() - CDATA_END.len()].to_string();
+    }
+
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        result.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let entity_end = match rest.find(';') {
+            Some(semi) => semi,
+            None => break,
+        };
+        let entity = &rest[1..entity_end];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ if entity.starts_with("#x") => u32::from_str_radix(&entity[2..], 16)
+                .ok()
+                .and_then(std::char::from_u32),
+            _ if entity.starts_with('#') => entity[1..].parse().ok().and_then(std::char::from_u32),
+            _ => None,
+        };
+        match decoded {
+            Some(c) => {
+                result.push(c);
+                rest = &rest[entity_end + 1..];
+            }
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Extracts the iCalendar text of every calendar-data element in a
+/// multistatus response, whatever namespace prefix the server chose.
+fn calendar_data(multistatus: &str) -> Vec<String> {
+    let mut calendars = Vec::new();
+    let mut rest = multistatus;
+    while let Some(open) = rest.find('<') {
+        rest = &rest[open + 1..];
+        let tag_end = match rest.find('>') {
+            Some(end) => end,
+            None => break,
+        };
+        let tag = &rest[..tag_end];
+        let name = tag.split_whitespace().next().unwrap_or("");
+        let local_name = name.rsplit(':').next().unwrap_or(name);
+        if local_name != CALENDAR_DATA_TAG || tag.ends_with('/') {
+            continue;
+        }
+
+        rest = &rest[tag_end + 1..];
+        let close = format!("</{}>", name);
+        match rest.find(&close) {
+            Some(content_end) => {
+                calendars.push(unescape_xml(&rest[..content_end]));
+                rest = &rest[content_end + close.len()..];
+            }
+            None => break,
+        }
+    }
+    calendars
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cal_machine::caldav::calendar_data;
+
+    #[test]
+    fn multistatus() {
+        let body = r#"<?xml version="1.0"?>
+<multistatus xmlns="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+  <response><propstat><prop>
+    <C:calendar-data>BEGIN:VCALENDAR&#13;
+SUMMARY:Fish &amp; chips
+END:VCALENDAR</C:calendar-data>
+  </prop></propstat></response>
+  <response><propstat><prop>
+    <cal:calendar-data xmlns:cal="urn:ietf:params:xml:ns:caldav"><![CDATA[BEGIN:VCALENDAR
+SUMMARY:<b>
+END:VCALENDAR]]></cal:calendar-data>
+  </prop></propstat></response>
+  <response><propstat><prop><C:calendar-data/></prop></propstat></response>
+</multistatus>"#;
+        assert_eq!(
+            calendar_data(body),
+            vec![
+                "BEGIN:VCALENDAR\r\nSUMMARY:Fish & chips\nEND:VCALENDAR",
+                "BEGIN:VCALENDAR\nSUMMARY:<b>\nEND:VCALENDAR"
+            ]
+        );
+    }
+}
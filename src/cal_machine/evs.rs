@@ -15,7 +15,7 @@ limitations under the License.
 */
 
 use crate::{
-    cal_machine::retriever::{self, EventsResponse},
+    cal_machine::retriever,
     cloneable, copyable, err, stm,
 };
 use chrono::{format::ParseError, offset::LocalResult, prelude::*, Duration};
@@ -48,16 +48,19 @@ pub enum PeriodMarker {
 }
 
 impl PeriodMarker {
+    pub fn all_day_start() -> PeriodMarker {
+        PeriodMarker::Start(NaiveTime::from_hms(0, 0, 0))
+    }
+
+    pub fn all_day_end() -> PeriodMarker {
+        PeriodMarker::End(NaiveTime::from_hms(23, 59, 59))
+    }
+
     pub fn select(
         &self,
         date_time: &Option<String>,
         date: &Option<String>,
     ) -> Result<DateTime<Local>, Error> {
-        let time = match self {
-            PeriodMarker::Start(time) => time,
-            PeriodMarker::End(time) => time,
-        };
-
         if let Some(inner_date_time) = date_time {
             Ok(inner_date_time.parse()?)
         } else if let Some(inner_date) = date {
@@ -66,19 +69,29 @@ impl PeriodMarker {
             let date_only =
                 NaiveDate::parse_from_str(inner_date, "%Y-%m-%d").expect("failed to parse");
             println!("{}", date_only);
-            let date_time = NaiveDateTime::new(date_only, *time);
-            let date_time_tz: DateTime<Local> = match Local.from_local_datetime(&date_time) {
-                LocalResult::None => Err(TimeZoneInvalidError())?,
-                LocalResult::Single(dt) => dt,
-                LocalResult::Ambiguous(dt_1, dt_2) => Err(TimeZoneAmbiguousError((dt_1, dt_2)))?,
-            };
-            println!("date: {:?}", date_time_tz);
-
-            Ok(date_time_tz)
+            self.on(date_only)
         } else {
             Err(MissingDateTimeError(*self).into())
         }
     }
+
+    /// The local time of this marker on an all-day event's date.
+    pub fn on(&self, date_only: NaiveDate) -> Result<DateTime<Local>, Error> {
+        let time = match self {
+            PeriodMarker::Start(time) => time,
+            PeriodMarker::End(time) => time,
+        };
+
+        let date_time = NaiveDateTime::new(date_only, *time);
+        let date_time_tz: DateTime<Local> = match Local.from_local_datetime(&date_time) {
+            LocalResult::None => Err(TimeZoneInvalidError())?,
+            LocalResult::Single(dt) => dt,
+            LocalResult::Ambiguous(dt_1, dt_2) => Err(TimeZoneAmbiguousError((dt_1, dt_2)))?,
+        };
+        println!("date: {:?}", date_time_tz);
+
+        Ok(date_time_tz)
+    }
 }
 
 type TwoDateTimes = (DateTime<Local>, DateTime<Local>);
@@ -100,6 +113,7 @@ pub trait DisplayableOccasion {
     //fn in_progress(&self, now: &Now) -> bool;
     fn partial_chron_cmp(&self, other: &Now) -> Option<Ordering>;
     //fn all_consuming(&self) -> bool;
+    fn tag(&self) -> Option<char>;
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -143,6 +157,10 @@ impl DisplayableOccasion for Minute {
             }
         }
     }
+
+    fn tag(&self) -> Option<char> {
+        None
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -152,6 +170,7 @@ pub struct Event {
     pub all_consuming: bool, //if an event is all consuming it is treated as being something that a person will be occupied with between the start and end date and the display should indicate that. Most events are treated as all consuming. All-day events aren't.
     pub start: StartDate,
     pub end: EndDate,
+    pub tag: Option<char>, //marks the calendar the event was read from
 }
 
 impl DisplayableOccasion for Event {
@@ -180,6 +199,10 @@ impl DisplayableOccasion for Event {
             None
         }
     }
+
+    fn tag(&self) -> Option<char> {
+        self.tag
+    }
 }
 
 impl Ord for Event {
@@ -187,7 +210,10 @@ impl Ord for Event {
         match self.start.as_ref().cmp(other.start.as_ref()) {
             Ordering::Equal => match self.end.as_ref().cmp(other.end.as_ref()) {
                 Ordering::Equal => match self.summary.cmp(&other.summary) {
-                    Ordering::Equal => self.description.cmp(&other.description),
+                    Ordering::Equal => match self.description.cmp(&other.description) {
+                        Ordering::Equal => self.tag.cmp(&other.tag),
+                        other => other,
+                    },
                     other => other,
                 },
                 other => other,
@@ -205,21 +231,26 @@ impl PartialOrd for Event {
 
 impl From<&retriever::Event> for Result<Event, Error> {
     fn from(ev: &retriever::Event) -> Result<Event, Error> {
+        let start = ev
+            .start
+            .as_ref()
+            .ok_or_else(|| MissingDateTimeError(PeriodMarker::all_day_start()))?;
+        let end = ev
+            .end
+            .as_ref()
+            .ok_or_else(|| MissingDateTimeError(PeriodMarker::all_day_end()))?;
         Ok(Event {
             summary: match &ev.summary {
                 Some(summary) => summary.to_string(),
                 None => "Unknown".to_string(),
             },
             description: ev.description.clone(),
-            all_consuming: !(ev.start.date_time.is_none() && ev.end.date_time.is_none()),
+            all_consuming: !(start.date_time.is_none() && end.date_time.is_none()),
             start: StartDate(
-                PeriodMarker::Start(NaiveTime::from_hms(0, 0, 0))
-                    .select(&ev.start.date_time, &ev.start.date)?,
-            ),
-            end: EndDate(
-                PeriodMarker::End(NaiveTime::from_hms(23, 59, 59))
-                    .select(&ev.end.date_time, &ev.end.date)?,
+                PeriodMarker::all_day_start().select(&start.date_time, &start.date)?,
             ),
+            end: EndDate(PeriodMarker::all_day_end().select(&end.date_time, &end.date)?),
+            tag: None,
         })
     }
 }
@@ -259,28 +290,31 @@ impl Appointments {
         }
     }
 
-    pub fn add(&mut self, received: &EventsResponse) -> Result<(), Error> {
+    pub fn add(&mut self, received: &[retriever::Event], tag: Option<char>) -> Result<(), Error> {
         self.state = Some({
             let mut state = self
                 .state
                 .take()
                 .expect("add(). Appointments.state is in an uninitialised state");
 
-            for ev in received.items.iter() {
-                state = match state {
-                    Uninitialised(st) => OneCreator(st.into(), Email(ev.creator.email.clone())),
-                    OneCreator(st, Email(email)) => {
-                        if email == ev.creator.email {
+            for ev in received.iter() {
+                state = match (state, &ev.creator) {
+                    (state, None) => state,
+                    (Uninitialised(st), Some(creator)) => {
+                        OneCreator(st.into(), Email(creator.email.clone()))
+                    }
+                    (OneCreator(st, Email(email)), Some(creator)) => {
+                        if email == creator.email {
                             OneCreator(st, Email(email))
                         } else {
                             NotOneCreator(st.into())
                         }
                     }
-                    NotOneCreator(st) => NotOneCreator(st),
+                    (NotOneCreator(st), _) => NotOneCreator(st),
                 };
                 let ev_res: Result<Event, Error> = ev.into();
                 let typed_ev = ev_res?;
-                self.events.push(typed_ev);
+                self.events.push(Event { tag, ..typed_ev });
             }
             state
         });
@@ -288,6 +322,13 @@ impl Appointments {
         Ok(())
     }
 
+    /// Adds events that were read from a calendar other than the
+    /// Google account. These have no bearing on the e-mail address
+    /// shown.
+    pub fn add_events(&mut self, events: Vec<Event>) {
+        self.events.extend(events);
+    }
+
     pub fn finalise(self) -> AppsReadonly {
         AppsReadonly {
             email: self.email(),
@@ -296,12 +337,18 @@ impl Appointments {
     }
 }
 
+#[derive(Clone)]
 pub struct AppsReadonly {
     email: Option<Email>,
     events: Vec<Event>,
 }
 
 impl AppsReadonly {
+    /// Appointments that were read earlier and saved.
+    pub fn saved(email: Option<Email>, events: Vec<Event>) -> AppsReadonly {
+        AppsReadonly { email, events }
+    }
+
     pub fn email(&self) -> Option<Email> {
         self.email.clone()
     }
@@ -0,0 +1,455 @@
+/*
+Copyright [2019] [Kieran White]
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::{
+    cal_machine::evs::{self, EndDate, Event, PeriodMarker, StartDate, TimeZoneInvalidError},
+    err,
+};
+use chrono::{offset::LocalResult, prelude::*, Duration};
+use chrono_tz::Tz;
+use log::{error, trace};
+
+const DATE_FORMAT: &str = "%Y%m%d";
+const FLOATING_FORMAT: &str = "%Y%m%dT%H%M%S";
+const UTC_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+const VEVENT: &str = "VEVENT";
+const CANCELLED: &str = "CANCELLED";
+const UNKNOWN_SUMMARY: &str = "Unknown";
+
+#[derive(Debug)]
+pub struct MalformedError(pub String);
+
+err!(Error {
+    Ev(evs::Error),
+    Malformed(MalformedError)
+});
+
+impl Error {
+    pub fn message(&self) -> String {
+        match self {
+            Error::Ev(err) => format!("{:?}", err),
+            Error::Malformed(MalformedError(property)) => format!("malformed {}", property),
+        }
+    }
+}
+
+impl From<TimeZoneInvalidError> for Error {
+    fn from(orig: TimeZoneInvalidError) -> Error {
+        Error::Ev(orig.into())
+    }
+}
+
+/// A single property of a component, e.g.
+/// DTSTART;TZID=Europe/Dublin:20190813T100000
+pub struct ContentLine {
+    pub name: String,
+    params: Vec<(String, String)>,
+    pub value: String,
+}
+
+impl ContentLine {
+    pub fn parse(line: &str) -> Option<ContentLine> {
+        let mut in_quotes = false;
+        let mut fields: Vec<String> = vec![String::new()];
+        let mut value_start = None;
+        for (idx, c) in line.char_indices() {
+            match c {
+                '"' => in_quotes = !in_quotes,
+                ';' if !in_quotes => fields.push(String::new()),
+                ':' if !in_quotes => {
+                    value_start = Some(idx + 1);
+                    break;
+                }
+                _ => fields.last_mut()?.push(c),
+            }
+        }
+
+        let value = line[value_start?..].to_string();
+        let mut fields = fields.into_iter();
+        let name = fields.next()?.to_ascii_uppercase();
+        let params = fields
+            .filter_map(|param| {
+                let mut key_val = param.splitn(2, '=');
+                Some((key_val.next()?.to_ascii_uppercase(), key_val.next()?.to_string()))
+            })
+            .collect();
+        Some(ContentLine {
+            name,
+            params,
+            value,
+        })
+    }
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, val)| val.as_str())
+    }
+}
+
+/// Joins folded lines, i.e. those continued on the next line by
+/// starting that line with a space or tab.
+pub fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for raw in text.lines() {
+        let raw = raw.trim_end_matches('\r');
+        if raw.starts_with(' ') || raw.starts_with('\t') {
+            if let Some(last) = lines.last_mut() {
+                last.push_str(&raw[1..]);
+                continue;
+            }
+        }
+        if !raw.is_empty() {
+            lines.push(raw.to_string());
+        }
+    }
+    lines
+}
+
+/// Splits the content lines of every VEVENT out of a calendar. Lines
+/// belonging to components nested within an event, such as alarms,
+/// are dropped.
+pub fn vevents(text: &str) -> Vec<Vec<ContentLine>> {
+    let mut events = Vec::new();
+    let mut current: Option<Vec<ContentLine>> = None;
+    let mut nested = 0;
+    for line in unfold(text).iter().filter_map(|line| ContentLine::parse(line)) {
+        let is_event = line.value.eq_ignore_ascii_case(VEVENT);
+        match (line.name.as_str(), current.take()) {
+            ("BEGIN", None) if is_event => current = Some(Vec::new()),
+            ("BEGIN", Some(props)) => {
+                nested += 1;
+                current = Some(props);
+            }
+            ("END", Some(props)) if nested == 0 && is_event => events.push(props),
+            ("END", Some(props)) => {
+                nested -= 1;
+                current = Some(props);
+            }
+            (_, Some(mut props)) => {
+                if nested == 0 {
+                    props.push(line);
+                }
+                current = Some(props);
+            }
+            (_, None) => {}
+        }
+    }
+    events
+}
+
+pub fn unescape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') | Some('N') => result.push('\n'),
+                Some(other) => result.push(other),
+                None => result.push(c),
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+/// Either an all-day DATE value or a DATE-TIME value resolved to the
+/// local time zone.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stamp {
+    Date(NaiveDate),
+    DateTime(DateTime<Local>),
+}
+
+fn local<T: TimeZone>(result: LocalResult<DateTime<T>>) -> Result<DateTime<Local>, Error> {
+    match result {
+        LocalResult::Single(dt) => Ok(dt.with_timezone(&Local)),
+        //the earlier of the two is what most calendar clients pick
+        LocalResult::Ambiguous(earliest, _) => Ok(earliest.with_timezone(&Local)),
+        LocalResult::None => Err(TimeZoneInvalidError().into()),
+    }
+}
+
+fn malformed(line: &ContentLine) -> Error {
+    MalformedError(format!("{}: {}", line.name, line.value)).into()
+}
+
+impl Stamp {
+    pub fn parse(line: &ContentLine) -> Result<Stamp, Error> {
+        let value = line.value.trim();
+        if line.param("VALUE") == Some("DATE") || value.len() == DATE_FORMAT.len() + 2 {
+            return NaiveDate::parse_from_str(value, DATE_FORMAT)
+                .map(Stamp::Date)
+                .map_err(|_| malformed(line));
+        }
+
+        if value.ends_with('Z') {
+            let utc = Utc
+                .datetime_from_str(value, UTC_FORMAT)
+                .map_err(|_| malformed(line))?;
+            return Ok(Stamp::DateTime(utc.with_timezone(&Local)));
+        }
+
+        let naive =
+            NaiveDateTime::parse_from_str(value, FLOATING_FORMAT).map_err(|_| malformed(line))?;
+        let zone = line
+            .param("TZID")
+            .map(|tzid| tzid.trim_start_matches('/'))
+            .and_then(|tzid| match tzid.parse::<Tz>() {
+                Ok(tz) => Some(tz),
+                Err(_) => {
+                    error!("unrecognised TZID {:?}, treating as local time", tzid);
+                    None
+                }
+            });
+        Ok(Stamp::DateTime(match zone {
+            Some(tz) => local(tz.from_local_datetime(&naive))?,
+            None => local(Local.from_local_datetime(&naive))?,
+        }))
+    }
+
+    fn add(&self, duration: Duration) -> Stamp {
+        match self {
+            Stamp::Date(date) => Stamp::Date(*date + duration),
+            Stamp::DateTime(date_time) => Stamp::DateTime(*date_time + duration),
+        }
+    }
+}
+
+/// Parses durations such as P1D, PT1H30M, P1W and -PT15M.
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let (negative, value) = match value.trim() {
+        v if v.starts_with('-') => (true, &v[1..]),
+        v if v.starts_with('+') => (false, &v[1..]),
+        v => (false, v),
+    };
+    if !value.starts_with('P') {
+        return None;
+    }
+
+    let mut total = Duration::zero();
+    let mut digits = String::new();
+    for c in value[1..].chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let amount = if c == 'T' {
+            continue;
+        } else {
+            digits.parse::<i64>().ok()?
+        };
+        digits.clear();
+        total = total
+            + match c {
+                'W' => Duration::weeks(amount),
+                'D' => Duration::days(amount),
+                'H' => Duration::hours(amount),
+                'M' => Duration::minutes(amount),
+                'S' => Duration::seconds(amount),
+                _ => return None,
+            };
+    }
+    if !digits.is_empty() {
+        return None;
+    }
+    Some(if negative { -total } else { total })
+}
+
+/// The properties of a VEVENT that are relevant for display.
+pub struct VEvent {
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    pub start: Stamp,
+    pub end: Stamp,
+    pub cancelled: bool,
+}
+
+impl VEvent {
+    pub fn new(props: &[ContentLine]) -> Result<VEvent, Error> {
+        let prop = |name: &str| props.iter().find(|line| line.name == name);
+
+        let start_line = prop("DTSTART")
+            .ok_or_else(|| Error::from(MalformedError("missing DTSTART".to_string())))?;
+        let start = Stamp::parse(start_line)?;
+        let end = if let Some(end_line) = prop("DTEND") {
+            Stamp::parse(end_line)?
+        } else if let Some(duration_line) = prop("DURATION") {
+            start.add(parse_duration(&duration_line.value).ok_or_else(|| malformed(duration_line))?)
+        } else if let Stamp::Date(_) = start {
+            start.add(Duration::days(1))
+        } else {
+            start
+        };
+
+        Ok(VEvent {
+            summary: prop("SUMMARY").map(|line| unescape(&line.value)),
+            description: prop("DESCRIPTION").map(|line| unescape(&line.value)),
+            start,
+            end,
+            cancelled: prop("STATUS")
+                .map(|line| line.value.eq_ignore_ascii_case(CANCELLED))
+                .unwrap_or(false),
+        })
+    }
+
+    pub fn to_event(&self) -> Result<Event, Error> {
+        let start = match self.start {
+            Stamp::Date(date) => PeriodMarker::all_day_start().on(date)?,
+            Stamp::DateTime(date_time) => date_time,
+        };
+        let end = match self.end {
+            //the end date of an all-day event is exclusive
+            Stamp::Date(date) => PeriodMarker::all_day_end().on(date.pred())?,
+            Stamp::DateTime(date_time) => date_time,
+        };
+
+        Ok(Event {
+            summary: self
+                .summary
+                .clone()
+                .unwrap_or_else(|| UNKNOWN_SUMMARY.to_string()),
+            description: self.description.clone(),
+            all_consuming: match self.start {
+                Stamp::Date(_) => false,
+                Stamp::DateTime(_) => true,
+            },
+            start: StartDate(start),
+            end: EndDate(end),
+            tag: None,
+        })
+    }
+}
+
+/// Parses every event in an iCalendar document. Events that can't be
+/// parsed are logged and skipped so that one bad event doesn't hide
+/// the rest of the calendar, and cancelled events are dropped.
+pub fn events(text: &str) -> Vec<Event> {
+    vevents(text)
+        .iter()
+        .filter_map(|props| match VEvent::new(props).and_then(|ev| {
+            if ev.cancelled {
+                Ok(None)
+            } else {
+                ev.to_event().map(Some)
+            }
+        }) {
+            Ok(ev) => ev,
+            Err(err) => {
+                error!("skipping unreadable event: {}", err.message());
+                None
+            }
+        })
+        .inspect(|ev| trace!("ical event: {:?}", ev))
+        .collect()
+}
+
+/// Parses the events in an iCalendar document that overlap the period
+/// from min_time to max_time.
+pub fn events_between(
+    text: &str,
+    min_time: &DateTime<Local>,
+    max_time: &DateTime<Local>,
+) -> Vec<Event> {
+    events(text)
+        .into_iter()
+        .filter(|ev| ev.start.as_ref() <= max_time && ev.end.as_ref() >= min_time)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cal_machine::ical::{events, parse_duration, unfold};
+    use chrono::{prelude::*, Duration};
+
+    const CALENDAR: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VEVENT\r
+UID:1\r
+DTSTART:20190813T090000Z\r
+DTEND:20190813T100000Z\r
+SUMMARY:Stand\r
+ up\\, daily\r
+BEGIN:VALARM\r
+DESCRIPTION:Alarm\r
+END:VALARM\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+DTSTART;VALUE=DATE:20190814\r
+DTEND;VALUE=DATE:20190816\r
+SUMMARY:Holiday\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+DTSTART;TZID=America/New_York:20190815T120000\r
+DURATION:PT1H30M\r
+DESCRIPTION:Two\\nlines\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+DTSTART:20190816T120000Z\r
+STATUS:CANCELLED\r
+SUMMARY:Gone\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:No start\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+    #[test]
+    fn parse() {
+        assert_eq!(unfold("A:b\n c\nD:e\n"), vec!["A:bc", "D:e"]);
+        assert_eq!(parse_duration("P1W2DT3H"), Some(Duration::hours(9 * 24 + 3)));
+        assert_eq!(parse_duration("-PT15M"), Some(Duration::minutes(-15)));
+        assert_eq!(parse_duration("1H"), None);
+
+        let evs = events(CALENDAR);
+        assert_eq!(evs.len(), 3);
+
+        assert_eq!(evs[0].summary, "Standup, daily");
+        assert!(evs[0].all_consuming);
+        assert_eq!(
+            evs[0].start.as_ref().with_timezone(&Utc),
+            Utc.ymd(2019, 8, 13).and_hms(9, 0, 0)
+        );
+        assert_eq!(
+            evs[0].end.as_ref().with_timezone(&Utc),
+            Utc.ymd(2019, 8, 13).and_hms(10, 0, 0)
+        );
+
+        assert_eq!(evs[1].summary, "Holiday");
+        assert!(!evs[1].all_consuming);
+        assert_eq!(
+            evs[1].start.as_ref(),
+            &Local.ymd(2019, 8, 14).and_hms(0, 0, 0)
+        );
+        assert_eq!(evs[1].end.as_ref(), &Local.ymd(2019, 8, 15).and_hms(23, 59, 59));
+
+        assert_eq!(evs[2].summary, "Unknown");
+        assert_eq!(evs[2].description, Some("Two\nlines".to_string()));
+        assert_eq!(
+            evs[2].start.as_ref().with_timezone(&Utc),
+            Utc.ymd(2019, 8, 15).and_hms(16, 0, 0)
+        );
+        assert_eq!(
+            evs[2].end.as_ref().with_timezone(&Utc),
+            Utc.ymd(2019, 8, 15).and_hms(17, 30, 0)
+        );
+    }
+}
@@ -0,0 +1,109 @@
+/*
+Copyright [2019] [Kieran White]
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::cal_machine::{evs::Event, ical};
+use chrono::prelude::*;
+use log::trace;
+use std::{
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+const ICS_EXTENSION: &str = "ics";
+
+/// Reads events from an iCalendar file or from every .ics file in a
+/// directory. The files are re-read each time so that changes to them
+/// are picked up on the next refresh.
+pub struct IcsFiles {
+    path: PathBuf,
+}
+
+impl IcsFiles {
+    pub fn new(path: &Path) -> IcsFiles {
+        IcsFiles {
+            path: path.to_path_buf(),
+        }
+    }
+
+    fn files(&self) -> io::Result<Vec<PathBuf>> {
+        if !self.path.is_dir() {
+            return Ok(vec![self.path.clone()]);
+        }
+
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&self.path)? {
+            let path = entry?.path();
+            if path.is_file() && path.extension() == Some(OsStr::new(ICS_EXTENSION)) {
+                files.push(path);
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    pub fn read(
+        &self,
+        min_time: &DateTime<Local>,
+        max_time: &DateTime<Local>,
+    ) -> io::Result<Vec<Event>> {
+        let mut events = Vec::new();
+        for file in self.files()? {
+            trace!("reading events from {:?}", file);
+            events.extend(ical::events_between(
+                &fs::read_to_string(&file)?,
+                min_time,
+                max_time,
+            ));
+        }
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{cal_machine::ics::IcsFiles, temp_dir::TempDir};
+    use chrono::prelude::*;
+    use std::fs;
+
+    #[test]
+    fn directory() {
+        let dir = TempDir::new("ics");
+        fs::write(
+            dir.join("a.ics"),
+            "BEGIN:VCALENDAR\nBEGIN:VEVENT\nDTSTART:20190813T090000\nDTEND:20190813T100000\nSUMMARY:Today\nEND:VEVENT\nBEGIN:VEVENT\nDTSTART:20190814T090000\nSUMMARY:Tomorrow\nEND:VEVENT\nEND:VCALENDAR\n",
+        )
+        .expect("a.ics");
+        fs::write(
+            dir.join("b.ics"),
+            "BEGIN:VCALENDAR\nBEGIN:VEVENT\nDTSTART;VALUE=DATE:20190812\nDTEND;VALUE=DATE:20190814\nSUMMARY:Away\nEND:VEVENT\nEND:VCALENDAR\n",
+        )
+        .expect("b.ics");
+        fs::write(dir.join("notes.txt"), "BEGIN:VEVENT").expect("notes.txt");
+
+        let day = Local.ymd(2019, 8, 13).and_hms(0, 0, 0);
+        let events = IcsFiles::new(&dir)
+            .read(&day, &day.with_hour(23).unwrap())
+            .expect("read");
+        let summaries: Vec<&str> = events.iter().map(|ev| ev.summary.as_str()).collect();
+        assert_eq!(summaries, vec!["Today", "Away"]);
+
+        let single = IcsFiles::new(&dir.join("b.ics"))
+            .read(&(day + chrono::Duration::days(1)), &(day + chrono::Duration::days(2)))
+            .expect("read file");
+        assert!(single.is_empty());
+    }
+}
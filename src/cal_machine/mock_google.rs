@@ -0,0 +1,255 @@
+/*
+Copyright [2019] [Kieran White]
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! A stand-in for the Google OAuth and Calendar services, so that the
+//! state machine can be driven through its edges without a network.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+pub const DEVICE_CODE_PATH: &str = "/o/oauth2/device/code";
+pub const TOKEN_PATH: &str = "/oauth2/v4/token";
+pub const PRIMARY_EVENTS_PATH: &str = "/calendar/v3/calendars/primary/events";
+
+/// A response to be given to a request.
+#[derive(Clone)]
+pub struct Canned {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl Canned {
+    pub fn new(status: u16, body: &str) -> Canned {
+        Canned {
+            status,
+            headers: Vec::new(),
+            body: body.to_string(),
+        }
+    }
+
+    /// Adds a header to the response.
+    pub fn with_header(mut self, name: &str, value: &str) -> Canned {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn device_code(user_code: &str) -> Canned {
+        Canned::new(
+            200,
+            &format!(
+                r#"{{"device_code": "device-1", "user_code": "{}", "expires_in": 1800,
+                "interval": 0, "verification_url": "https://www.google.com/device"}}"#,
+                user_code
+            ),
+        )
+    }
+
+    pub fn poll_error(status: u16, error: &str) -> Canned {
+        Canned::new(
+            status,
+            &format!(r#"{{"error": "{}", "error_description": "{}"}}"#, error, error),
+        )
+    }
+
+    pub fn tokens(refresh_token: &str) -> Canned {
+        Canned::new(
+            200,
+            &format!(
+                r#"{{"access_token": "access-1", "refresh_token": "{}",
+                "expires_in": 3600, "token_type": "Bearer"}}"#,
+                refresh_token
+            ),
+        )
+    }
+
+    pub fn refreshed() -> Canned {
+        Canned::new(
+            200,
+            r#"{"access_token": "access-2", "expires_in": 3600, "token_type": "Bearer"}"#,
+        )
+    }
+
+    /// A page of hour long events on date, formatted as %Y-%m-%d.
+    pub fn events(date: &str, summaries: &[&str], next_page: Option<&str>) -> Canned {
+        let items: Vec<String> = summaries
+            .iter()
+            .map(|summary| {
+                format!(
+                    r#"{{"id": "{}", "summary": "{}", "creator": {{"email": "me@example.com"}},
+                    "start": {{"dateTime": "{}T09:00:00Z"}}, "end": {{"dateTime": "{}T10:00:00Z"}}}}"#,
+                    summary, summary, date, date
+                )
+            })
+            .collect();
+        let next_page = match next_page {
+            Some(token) => format!(r#""nextPageToken": "{}","#, token),
+            None => r#""nextSyncToken": "sync-1","#.to_string(),
+        };
+        Canned::new(
+            200,
+            &format!(r#"{{{} "items": [{}]}}"#, next_page, items.join(",")),
+        )
+    }
+}
+
+type Script = HashMap<String, VecDeque<Canned>>;
+
+/// The headers of a request, by their names in lower case.
+pub type Headers = HashMap<String, String>;
+
+struct Log {
+    requests: Arc<Mutex<Vec<String>>>,
+    headers: Arc<Mutex<Vec<Headers>>>,
+}
+
+/// Serves the responses scripted for each path in turn, repeating the
+/// last one once the others have been used. Unscripted paths are not
+/// found.
+pub struct MockGoogle {
+    addr: SocketAddr,
+    requests: Arc<Mutex<Vec<String>>>,
+    headers: Arc<Mutex<Vec<Headers>>>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl MockGoogle {
+    pub fn start(script: Vec<(&str, Vec<Canned>)>) -> MockGoogle {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind mock server");
+        let addr = listener.local_addr().expect("mock server address");
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let headers = Arc::new(Mutex::new(Vec::new()));
+        let stopped = Arc::new(AtomicBool::new(false));
+        let mut script: Script = script
+            .into_iter()
+            .map(|(path, responses)| (path.to_string(), responses.into_iter().collect()))
+            .collect();
+
+        let log = Log {
+            requests: requests.clone(),
+            headers: headers.clone(),
+        };
+        let stop = stopped.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if stop.load(Ordering::SeqCst) {
+                    break;
+                }
+                if let Ok(stream) = stream {
+                    MockGoogle::serve(stream, &mut script, &log);
+                }
+            }
+        });
+
+        MockGoogle {
+            addr,
+            requests,
+            headers,
+            stopped,
+        }
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// The method and target of each request received so far.
+    pub fn requests(&self) -> Arc<Mutex<Vec<String>>> {
+        self.requests.clone()
+    }
+
+    /// The headers of each request received so far.
+    pub fn headers(&self) -> Arc<Mutex<Vec<Headers>>> {
+        self.headers.clone()
+    }
+
+    fn serve(stream: TcpStream, script: &mut Script, log: &Log) {
+        let mut reader = BufReader::new(stream);
+        let mut request_line = String::new();
+        if reader.read_line(&mut request_line).is_err() {
+            return;
+        }
+        let mut headers = Headers::new();
+        loop {
+            let mut header = String::new();
+            match reader.read_line(&mut header) {
+                Ok(0) | Err(_) => return,
+                Ok(_) if header.trim().is_empty() => break,
+                Ok(_) => {
+                    let mut parts = header.splitn(2, ':');
+                    if let (Some(name), Some(value)) = (parts.next(), parts.next()) {
+                        headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+                    }
+                }
+            }
+        }
+        let content_length = headers
+            .get("content-length")
+            .and_then(|len| len.parse().ok())
+            .unwrap_or(0);
+        let mut body = vec![0; content_length];
+        if reader.read_exact(&mut body).is_err() {
+            return;
+        }
+
+        let mut parts = request_line.split_whitespace();
+        let method = parts.next().unwrap_or_default();
+        let target = parts.next().unwrap_or_default();
+        log.requests
+            .lock()
+            .expect("mock request log")
+            .push(format!("{} {}", method, target));
+        log.headers.lock().expect("mock header log").push(headers);
+
+        let path = target.split('?').next().unwrap_or_default();
+        let canned = match script.get_mut(path) {
+            Some(responses) if responses.len() > 1 => responses.pop_front(),
+            Some(responses) => responses.front().cloned(),
+            None => None,
+        }
+        .unwrap_or_else(|| Canned::new(404, "{}"));
+
+        let extra: String = canned
+            .headers
+            .iter()
+            .map(|(name, value)| format!("{}: {}\r\n", name, value))
+            .collect();
+        let response = format!(
+            "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+            canned.status,
+            canned.body.len(),
+            extra,
+            canned.body
+        );
+        let _ = reader.get_mut().write_all(response.as_bytes());
+    }
+}
+
+impl Drop for MockGoogle {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        //wake the listener so that it notices
+        let _ = TcpStream::connect(self.addr);
+    }
+}
@@ -17,23 +17,37 @@ limitations under the License.
 use chrono::prelude::*;
 use reqwest::{self, Client, Response};
 use serde::Deserialize;
+use std::{
+    env,
+    fs::File,
+    io::{self, BufReader},
+    path::Path,
+};
+use url::Url;
 
-const DEVICE_CODE_URL: &str = "https://accounts.google.com/o/oauth2/device/code";
-const AUTHORISATION_URL: &str = "https://www.googleapis.com/oauth2/v4/token";
-const READ_URL: &str = "https://www.googleapis.com/calendar/v3/calendars/primary/events";
+pub const ACCOUNTS_URL_VAR: &str = "CALENDAR_MIRROR_ACCOUNTS_URL";
+pub const API_URL_VAR: &str = "CALENDAR_MIRROR_API_URL";
+const ACCOUNTS_URL: &str = "https://accounts.google.com";
+const API_URL: &str = "https://www.googleapis.com";
+const DEVICE_CODE_PATH: &str = "/o/oauth2/device/code";
+const AUTHORISATION_PATH: &str = "/oauth2/v4/token";
+const CALENDARS_PATH: &str = "/calendar/v3/calendars";
+const EVENTS_SEGMENT: &str = "events";
+const CALENDAR_LIST_PATH: &str = "/calendar/v3/users/me/calendarList";
+pub const CLIENT_SECRET_FILE: &str = "client_secret.json";
+pub const CLIENT_ID_VAR: &str = "CALENDAR_MIRROR_CLIENT_ID";
+pub const CLIENT_SECRET_VAR: &str = "CALENDAR_MIRROR_CLIENT_SECRET";
 const CLIENT_ID_KEY: &str = "client_id";
-const CLIENT_ID_VAL: &str =
-    "873648397769-eba22ohhel0t30e37dib506540vjdb25.apps.googleusercontent.com";
 const SCOPE_KEY: &str = "scope";
 const SCOPE_VAL: &str = "https://www.googleapis.com/auth/calendar.readonly";
 const CLIENT_SECRET_KEY: &str = "client_secret";
-const CLIENT_SECRET_VAL: &str = "miaNRI8ECPVbiAaKWgiw6a3S";
 const CODE_KEY: &str = "code";
 const GRANT_TYPE_KEY: &str = "grant_type";
 const GRANT_TYPE_POLL_VAL: &str = "http://oauth.net/grant_type/device/1.0";
 const GRANT_TYPE_REFRESH_VAL: &str = "refresh_token";
 const REFRESH_TOKEN_KEY: &str = "refresh_token";
 const PAGE_TOKEN_KEY: &str = "pageToken";
+const SYNC_TOKEN_KEY: &str = "syncToken";
 
 const TIME_MIN_KEY: &str = "timeMin";
 const TIME_MAX_KEY: &str = "timeMax";
@@ -52,33 +66,126 @@ pub const POLLING_TOO_FREQUENTLY_ERROR: &str = "slow_down";
 #[derive(Debug)]
 pub struct PageToken(pub String);
 
+#[derive(Clone, Debug)]
+pub struct SyncToken(pub String);
+
+/// The next page of events to be read from one of the Google calendars.
+#[derive(Debug)]
+pub struct CalendarPage {
+    pub calendar_id: String,
+    pub page_token: Option<PageToken>,
+    pub sync_token: Option<SyncToken>,
+}
+
+impl CalendarPage {
+    pub fn first(calendar_id: String, sync_token: Option<SyncToken>) -> CalendarPage {
+        CalendarPage {
+            calendar_id,
+            page_token: None,
+            sync_token,
+        }
+    }
+}
+
+/// Where the Google services are found. Each host can be replaced
+/// through the environment, e.g. by a local server standing in for
+/// Google while testing.
+#[derive(Clone, Debug)]
+pub struct Endpoints {
+    device_code: Url,
+    authorisation: Url,
+    calendars: Url,
+    calendar_list: Url,
+}
+
+impl Endpoints {
+    pub fn new(accounts_url: &str, api_url: &str) -> Result<Endpoints, url::ParseError> {
+        let accounts_url = accounts_url.trim_end_matches('/');
+        let api_url = api_url.trim_end_matches('/');
+        Ok(Endpoints {
+            device_code: Url::parse(&format!("{}{}", accounts_url, DEVICE_CODE_PATH))?,
+            authorisation: Url::parse(&format!("{}{}", api_url, AUTHORISATION_PATH))?,
+            calendars: Url::parse(&format!("{}{}", api_url, CALENDARS_PATH))?,
+            calendar_list: Url::parse(&format!("{}{}", api_url, CALENDAR_LIST_PATH))?,
+        })
+    }
+
+    pub fn from_env() -> Result<Endpoints, url::ParseError> {
+        Endpoints::new(
+            &env::var(ACCOUNTS_URL_VAR).unwrap_or_else(|_| ACCOUNTS_URL.to_string()),
+            &env::var(API_URL_VAR).unwrap_or_else(|_| API_URL.to_string()),
+        )
+    }
+}
+
+/// The OAuth2 client that the user authorises to read their calendar.
+#[derive(Clone, Deserialize, Debug)]
+pub struct ClientCredentials {
+    client_id: String,
+    client_secret: String,
+}
+
+/// The layout of the client secret file downloaded from the Google
+/// developer console.
+#[derive(Deserialize)]
+struct InstalledClient {
+    installed: ClientCredentials,
+}
+
+impl ClientCredentials {
+    /// Takes the credentials from the environment if both are set there
+    /// and otherwise from the client secret file at path.
+    pub fn load(path: &Path) -> io::Result<ClientCredentials> {
+        let credentials = match (env::var(CLIENT_ID_VAR), env::var(CLIENT_SECRET_VAR)) {
+            (Ok(client_id), Ok(client_secret)) => ClientCredentials {
+                client_id,
+                client_secret,
+            },
+            _ => {
+                let client: InstalledClient =
+                    serde_json::from_reader(BufReader::new(File::open(path)?))?;
+                client.installed
+            }
+        };
+
+        if credentials.client_id.trim().is_empty() || credentials.client_secret.trim().is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the client ID and secret must not be empty",
+            ));
+        }
+        Ok(credentials)
+    }
+}
+
 pub struct EventRetriever {
     client: Client,
+    endpoints: Endpoints,
 }
 
 impl EventRetriever {
-    pub fn inst() -> EventRetriever {
+    pub fn new(endpoints: Endpoints) -> EventRetriever {
         let client = Client::new();
 
-        EventRetriever { client }
+        EventRetriever { client, endpoints }
     }
 
-    pub fn retrieve_dev_and_code(&self) -> reqwest::Result<Response> {
-        let post_args = [(CLIENT_ID_KEY, CLIENT_ID_VAL), (SCOPE_KEY, SCOPE_VAL)];
+    pub fn retrieve_dev_and_code(&self, client: &ClientCredentials) -> reqwest::Result<Response> {
+        let post_args = [(CLIENT_ID_KEY, client.client_id.as_str()), (SCOPE_KEY, SCOPE_VAL)];
         println!("device code args: {:?}", post_args);
-        let request = self.client.post(DEVICE_CODE_URL).form(&post_args);
+        let request = self.client.post(self.endpoints.device_code.clone()).form(&post_args);
         println!("device code request: {:?}", request);
         request.send()
     }
 
-    pub fn poll(&self, code: &str) -> reqwest::Result<Response> {
+    pub fn poll(&self, client: &ClientCredentials, code: &str) -> reqwest::Result<Response> {
         let post_args = [
-            (CLIENT_ID_KEY, CLIENT_ID_VAL),
-            (CLIENT_SECRET_KEY, CLIENT_SECRET_VAL),
+            (CLIENT_ID_KEY, client.client_id.as_str()),
+            (CLIENT_SECRET_KEY, client.client_secret.as_str()),
             (CODE_KEY, code),
             (GRANT_TYPE_KEY, GRANT_TYPE_POLL_VAL),
         ];
-        self.client.post(AUTHORISATION_URL).form(&post_args).send()
+        self.client.post(self.endpoints.authorisation.clone()).form(&post_args).send()
     }
 
     pub fn read(
@@ -86,43 +193,72 @@ impl EventRetriever {
         bearer: &str,
         min_time: &DateTime<Local>,
         max_time: &DateTime<Local>,
-        page_token: &Option<PageToken>,
+        page: &CalendarPage,
+        page_size: u32,
     ) -> reqwest::Result<Response> {
         let min = &min_time.format("%+").to_string().clone();
         let max = &max_time.format("%+").to_string().clone();
+        //calendar ids can contain characters such as # that need to be escaped
+        let mut read_url = self.endpoints.calendars.clone();
+        read_url
+            .path_segments_mut()
+            .expect("calendars url cannot be a base")
+            .push(&page.calendar_id)
+            .push(EVENTS_SEGMENT);
         let request = self
             .client
-            .get(READ_URL)
+            .get(read_url)
             .header(ACCEPT_HEADER, ACCEPT_JSON)
             .header(AUTHORISATION_HEADER, bearer);
-        let request = match page_token {
-            None => request.query(&[
-                (TIME_MIN_KEY, min),
-                (TIME_MAX_KEY, max),
-                (MAX_RESULTS_KEY, &String::from("1")),
-                (SINGLE_EVENTS_KEY, &String::from("true")),
-            ]),
-            Some(PageToken(token)) => request.query(&[
-                (TIME_MIN_KEY, min),
-                (TIME_MAX_KEY, max),
-                (MAX_RESULTS_KEY, &String::from("1")),
-                (SINGLE_EVENTS_KEY, &String::from("true")),
-                (PAGE_TOKEN_KEY, token),
-            ]),
+        let request = request.query(&[
+            (MAX_RESULTS_KEY, &page_size.to_string()),
+            (SINGLE_EVENTS_KEY, &String::from("true")),
+        ]);
+        //Google doesn't allow a period to be given along with a sync
+        //token, so changes to events on any date are returned
+        let request = match &page.sync_token {
+            None => request.query(&[(TIME_MIN_KEY, min), (TIME_MAX_KEY, max)]),
+            Some(SyncToken(token)) => request.query(&[(SYNC_TOKEN_KEY, token)]),
+        };
+        let request = match &page.page_token {
+            None => request,
+            Some(PageToken(token)) => request.query(&[(PAGE_TOKEN_KEY, token)]),
         };
         println!("cal read request: {:?}", request);
         request.send()
     }
 
-    pub fn refresh(&self, refresh_token: &str) -> reqwest::Result<Response> {
+    pub fn list_calendars(
+        &self,
+        bearer: &str,
+        page_token: &Option<PageToken>,
+    ) -> reqwest::Result<Response> {
+        let request = self
+            .client
+            .get(self.endpoints.calendar_list.clone())
+            .header(ACCEPT_HEADER, ACCEPT_JSON)
+            .header(AUTHORISATION_HEADER, bearer);
+        let request = match page_token {
+            None => request,
+            Some(PageToken(token)) => request.query(&[(PAGE_TOKEN_KEY, token)]),
+        };
+        println!("calendar list request: {:?}", request);
+        request.send()
+    }
+
+    pub fn refresh(
+        &self,
+        client: &ClientCredentials,
+        refresh_token: &str,
+    ) -> reqwest::Result<Response> {
         let post_args = [
-            (CLIENT_ID_KEY, CLIENT_ID_VAL),
-            (CLIENT_SECRET_KEY, CLIENT_SECRET_VAL),
+            (CLIENT_ID_KEY, client.client_id.as_str()),
+            (CLIENT_SECRET_KEY, client.client_secret.as_str()),
             (REFRESH_TOKEN_KEY, refresh_token),
             (GRANT_TYPE_KEY, GRANT_TYPE_REFRESH_VAL),
         ];
         println!("refresh request args: {:?}", post_args);
-        let request = self.client.post(AUTHORISATION_URL).form(&post_args);
+        let request = self.client.post(self.endpoints.authorisation.clone()).form(&post_args);
         println!("refresh token request: {:?}", request);
         request.send()
     }
@@ -164,7 +300,9 @@ pub struct PollErrorResponse {
     pub error_description: String,
 }
 
-#[derive(Deserialize, Debug)]
+pub const CANCELLED_STATUS: &str = "cancelled";
+
+#[derive(Clone, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct DateTimeStamp {
     //#[serde(rename = "dateTime")]
@@ -172,23 +310,41 @@ pub struct DateTimeStamp {
     pub date: Option<String> //but all-day events only provide the date
 }
 
-#[derive(Deserialize, Debug, Eq, PartialEq)]
+#[derive(Clone, Deserialize, Debug, Eq, PartialEq)]
 pub struct PersonalIdentifier {
     pub email: String,
 }
 
-#[derive(Deserialize, Debug)]
+//Events deleted since a sync token was issued only have an id and a
+//cancelled status
+#[derive(Clone, Deserialize, Debug)]
 pub struct Event {
+    pub id: String,
+    pub status: Option<String>,
     pub summary: Option<String>,
     pub description: Option<String>,
-    pub creator: PersonalIdentifier,
-    pub start: DateTimeStamp,
-    pub end: DateTimeStamp,
+    pub creator: Option<PersonalIdentifier>,
+    pub start: Option<DateTimeStamp>,
+    pub end: Option<DateTimeStamp>,
 }
 
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct EventsResponse {
     pub next_page_token: Option<String>,
+    pub next_sync_token: Option<String>,
     pub items: Vec<Event>,
 }
+
+#[derive(Deserialize, Debug)]
+pub struct CalendarListEntry {
+    pub id: String,
+    pub selected: Option<bool>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CalendarListResponse {
+    pub next_page_token: Option<String>,
+    pub items: Vec<CalendarListEntry>,
+}
@@ -0,0 +1,334 @@
+/*
+Copyright [2019] [Kieran White]
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::{
+    cal_machine::{
+        caldav::{self, CalDav, Credentials},
+        evs::{Appointments, Event},
+        ics::IcsFiles,
+        webcal::{self, Webcal},
+    },
+    err,
+};
+use chrono::prelude::*;
+use log::error;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader},
+    mem,
+    path::{Path, PathBuf},
+};
+
+err!(Error {
+    CalDav(caldav::Error),
+    IO(io::Error),
+    Webcal(webcal::Error)
+});
+
+impl Error {
+    pub fn message(&self) -> String {
+        match self {
+            Error::CalDav(err) => format!("CalDAV {}", err.message()),
+            Error::IO(err) => err.to_string(),
+            Error::Webcal(err) => format!("webcal {}", err.message()),
+        }
+    }
+}
+
+/// A calendar, other than the Google account, whose events are shown
+/// alongside the Google ones. Sources are read on the network worker.
+pub trait EventSource: Send {
+    fn read(
+        &mut self,
+        min_time: &DateTime<Local>,
+        max_time: &DateTime<Local>,
+    ) -> Result<Vec<Event>, Error>;
+
+    /// Marks anything the source keeps between reads as out of date.
+    fn refresh(&mut self) {}
+}
+
+impl EventSource for CalDav {
+    fn read(
+        &mut self,
+        min_time: &DateTime<Local>,
+        max_time: &DateTime<Local>,
+    ) -> Result<Vec<Event>, Error> {
+        Ok(CalDav::read(self, min_time, max_time)?)
+    }
+}
+
+impl EventSource for IcsFiles {
+    fn read(
+        &mut self,
+        min_time: &DateTime<Local>,
+        max_time: &DateTime<Local>,
+    ) -> Result<Vec<Event>, Error> {
+        Ok(IcsFiles::read(self, min_time, max_time)?)
+    }
+}
+
+impl EventSource for Webcal {
+    fn read(
+        &mut self,
+        min_time: &DateTime<Local>,
+        max_time: &DateTime<Local>,
+    ) -> Result<Vec<Event>, Error> {
+        Ok(Webcal::read(self, min_time, max_time)?)
+    }
+
+    fn refresh(&mut self) {
+        Webcal::refresh(self)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CalDavConfig {
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub bearer: Option<String>,
+}
+
+impl From<&CalDavConfig> for Credentials {
+    fn from(config: &CalDavConfig) -> Credentials {
+        match (&config.bearer, &config.username) {
+            (Some(token), _) => Credentials::Bearer(token.clone()),
+            (None, Some(username)) => Credentials::Basic(username.clone(), config.password.clone()),
+            (None, None) => Credentials::Anonymous,
+        }
+    }
+}
+
+const PRIMARY_CALENDAR: &str = "primary";
+const DEFAULT_GOOGLE_PAGE_SIZE: u32 = 250;
+const MAX_GOOGLE_PAGE_SIZE: u32 = 2500;
+
+fn google_default() -> bool {
+    true
+}
+
+fn google_page_size_default() -> u32 {
+    DEFAULT_GOOGLE_PAGE_SIZE
+}
+
+fn google_calendars_default() -> Vec<String> {
+    vec![PRIMARY_CALENDAR.to_string()]
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct CalendarsConfig {
+    #[serde(default = "google_default")]
+    pub google: bool,
+    #[serde(default = "google_calendars_default")]
+    pub google_calendars: Vec<String>,
+    #[serde(default)]
+    pub google_calendar_list: bool,
+    /// The maximum number of events read from Google in each request.
+    #[serde(default = "google_page_size_default")]
+    pub google_page_size: u32,
+    #[serde(default)]
+    pub caldav: Vec<CalDavConfig>,
+    #[serde(default)]
+    pub ics: Vec<PathBuf>,
+    #[serde(default)]
+    pub webcal: Vec<String>,
+    /// One character markers keyed by Google calendar ID, CalDAV or
+    /// feed URL or ics path.
+    #[serde(default)]
+    pub tags: HashMap<String, char>,
+}
+
+impl Default for CalendarsConfig {
+    fn default() -> CalendarsConfig {
+        CalendarsConfig {
+            google: google_default(),
+            google_calendars: google_calendars_default(),
+            google_calendar_list: false,
+            google_page_size: google_page_size_default(),
+            caldav: Vec::new(),
+            ics: Vec::new(),
+            webcal: Vec::new(),
+            tags: HashMap::new(),
+        }
+    }
+}
+
+impl CalendarsConfig {
+    /// A missing file means there are no calendars besides the Google one.
+    pub fn load(path: &Path) -> io::Result<CalendarsConfig> {
+        match File::open(path) {
+            Ok(file) => {
+                let config: CalendarsConfig = serde_json::from_reader(BufReader::new(file))?;
+                config
+                    .validate()
+                    .map_err(|msg| io::Error::new(io::ErrorKind::InvalidData, msg))?;
+                Ok(config)
+            }
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => {
+                Ok(CalendarsConfig::default())
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Why the calendars can't be read as configured, if they can't.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.google_page_size == 0 || self.google_page_size > MAX_GOOGLE_PAGE_SIZE {
+            return Err(format!(
+                "google_page_size must be between 1 and {}",
+                MAX_GOOGLE_PAGE_SIZE
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn calendars(&self) -> Calendars {
+        let caldav = self.caldav.iter().map(|config| {
+            (
+                config.url.clone(),
+                Box::new(CalDav::new(&config.url, config.into())) as Box<dyn EventSource>,
+            )
+        });
+        let ics = self.ics.iter().map(|path| {
+            (
+                path.to_string_lossy().into_owned(),
+                Box::new(IcsFiles::new(path)) as Box<dyn EventSource>,
+            )
+        });
+        let webcal = self
+            .webcal
+            .iter()
+            .map(|url| (url.clone(), Box::new(Webcal::new(url)) as Box<dyn EventSource>));
+        Calendars {
+            google: self.google,
+            google_calendars: if self.google_calendar_list {
+                GoogleCalendars::Selected
+            } else {
+                GoogleCalendars::Listed(self.google_calendars.clone())
+            },
+            google_page_size: self.google_page_size,
+            sources: Sources {
+                others: caldav.chain(ics).chain(webcal).collect(),
+                tags: self.tags.clone(),
+            },
+            tags: self.tags.clone(),
+        }
+    }
+}
+
+/// Which calendars of the Google account are shown.
+#[derive(Clone)]
+pub enum GoogleCalendars {
+    Listed(Vec<String>),
+    /// Those selected in the account's calendar list, i.e. the ones
+    /// shown by Google Calendar itself.
+    Selected,
+}
+
+/// The calendars to be shown. When the Google calendar is disabled the
+/// mirror runs entirely from the other sources, without needing to be
+/// authorised.
+pub struct Calendars {
+    pub google: bool,
+    pub google_calendars: GoogleCalendars,
+    pub google_page_size: u32,
+    pub sources: Sources,
+    tags: HashMap<String, char>,
+}
+
+impl Calendars {
+    pub fn tag(&self, calendar: &str) -> Option<char> {
+        self.tags.get(calendar).cloned()
+    }
+
+    /// Hands over the other sources, to be read off the thread that
+    /// polls the buttons, leaving none behind.
+    pub fn take_sources(&mut self) -> Sources {
+        mem::replace(
+            &mut self.sources,
+            Sources {
+                others: Vec::new(),
+                tags: self.tags.clone(),
+            },
+        )
+    }
+
+    /// Adds the events of the other sources on the day starting at date.
+    pub fn read_day(&mut self, date: &DateTime<Local>, apps: &mut Appointments) {
+        apps.add_events(self.sources.read_day(date));
+    }
+}
+
+/// The calendars other than the Google account, with their tags.
+#[derive(Default)]
+pub struct Sources {
+    pub others: Vec<(String, Box<dyn EventSource>)>,
+    tags: HashMap<String, char>,
+}
+
+impl Sources {
+    /// Has the sources read afresh the next time a day is read.
+    pub fn refresh(&mut self) {
+        for (_name, source) in self.others.iter_mut() {
+            source.refresh();
+        }
+    }
+
+    /// The events of every source on the day starting at date. A
+    /// source that can't be read is logged and left out.
+    pub fn read_day(&mut self, date: &DateTime<Local>) -> Vec<Event> {
+        let day_end = *date + chrono::Duration::days(1) - chrono::Duration::seconds(1);
+        let mut day = Vec::new();
+        for (name, source) in self.others.iter_mut() {
+            match source.read(date, &day_end) {
+                Ok(events) => {
+                    let tag = self.tags.get(name.as_str()).cloned();
+                    day.extend(events.into_iter().map(|ev| Event { tag, ..ev }))
+                }
+                Err(err) => error!("failed to read calendar source {}: {}", name, err.message()),
+            }
+        }
+        day
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{cal_machine::sources::CalendarsConfig, temp_dir::TempDir};
+    use std::fs;
+
+    #[test]
+    fn load_validates() {
+        let dir = TempDir::new("calendars");
+        let path = dir.join("calendars.json");
+        let load = |json: &str| {
+            fs::write(&path, json).expect("calendars file");
+            CalendarsConfig::load(&path)
+        };
+
+        let config = load(r#"{"google_page_size": 2500, "webcal": ["webcal://example.com/a.ics"]}"#)
+            .expect("valid calendars");
+        assert_eq!(config.google_page_size, 2500);
+        assert_eq!(config.webcal.len(), 1);
+        assert!(load(r#"{"google_page_size": 0}"#).is_err());
+        assert!(load(r#"{"google_page_size": 2501}"#).is_err());
+        assert!(load(r#"{"google_pagesize": 10}"#).is_err());
+    }
+}
@@ -0,0 +1,165 @@
+/*
+Copyright [2019] [Kieran White]
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::cal_machine::{
+    evs::{self, Appointments},
+    retriever::{self, EventsResponse, SyncToken, CANCELLED_STATUS},
+};
+use chrono::prelude::*;
+use log::trace;
+use std::collections::BTreeMap;
+
+/// The events of one Google calendar on the displayed date, kept
+/// between refreshes so that only changes need to be downloaded.
+struct SyncedDay {
+    date: DateTime<Local>,
+    sync_token: Option<SyncToken>,
+    items: BTreeMap<String, retriever::Event>,
+}
+
+pub struct SyncedCalendars {
+    days: BTreeMap<String, SyncedDay>,
+}
+
+impl SyncedCalendars {
+    pub fn new() -> SyncedCalendars {
+        SyncedCalendars {
+            days: BTreeMap::new(),
+        }
+    }
+
+    /// The token to read changes with, if the calendar's events on date
+    /// have already been read in full.
+    pub fn sync_token(&self, calendar_id: &str, date: &DateTime<Local>) -> Option<SyncToken> {
+        self.days
+            .get(calendar_id)
+            .filter(|day| day.date == *date)
+            .and_then(|day| day.sync_token.clone())
+    }
+
+    pub fn forget(&mut self, calendar_id: &str) {
+        self.days.remove(calendar_id);
+    }
+
+    pub fn retain(&mut self, calendar_ids: &[String]) {
+        self.days.retain(|id, _| calendar_ids.contains(id));
+    }
+
+    /// Applies a page of events to the calendar's day. A page starting a
+    /// full read replaces whatever was known about the calendar.
+    pub fn update(
+        &mut self,
+        calendar_id: &str,
+        date: &DateTime<Local>,
+        restart: bool,
+        received: &EventsResponse,
+    ) -> Result<(), evs::Error> {
+        if restart {
+            self.days.remove(calendar_id);
+        }
+        let day = self
+            .days
+            .entry(calendar_id.to_string())
+            .or_insert_with(|| SyncedDay {
+                date: *date,
+                sync_token: None,
+                items: BTreeMap::new(),
+            });
+        let day_end = *date + chrono::Duration::days(1) - chrono::Duration::seconds(1);
+
+        for item in received.items.iter() {
+            if item.status.as_deref() == Some(CANCELLED_STATUS) {
+                trace!("removing event {}", item.id);
+                day.items.remove(&item.id);
+                continue;
+            }
+
+            //changes to events on other dates are returned too
+            let ev: Result<evs::Event, evs::Error> = item.into();
+            let ev = ev?;
+            if *ev.start.as_ref() <= day_end && ev.end.as_ref() >= date {
+                day.items.insert(item.id.clone(), item.clone());
+            } else {
+                day.items.remove(&item.id);
+            }
+        }
+
+        if let Some(token) = &received.next_sync_token {
+            day.sync_token = Some(SyncToken(token.clone()));
+        }
+        Ok(())
+    }
+
+    pub fn appointments(
+        &self,
+        date: &DateTime<Local>,
+        tag: impl Fn(&str) -> Option<char>,
+    ) -> Result<Appointments, evs::Error> {
+        let mut apps = Appointments::new();
+        for (calendar_id, day) in self.days.iter().filter(|(_, day)| day.date == *date) {
+            let items: Vec<retriever::Event> = day.items.values().cloned().collect();
+            apps.add(&items, tag(calendar_id))?;
+        }
+        Ok(apps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cal_machine::{retriever::EventsResponse, synced::SyncedCalendars};
+    use chrono::prelude::*;
+
+    fn response(json: &str) -> EventsResponse {
+        serde_json::from_str(json).expect("events response")
+    }
+
+    #[test]
+    fn incremental() {
+        let date = Local.ymd(2019, 8, 13).and_hms(0, 0, 0);
+        let mut synced = SyncedCalendars::new();
+        let full = response(
+            r#"{"nextSyncToken": "one", "items": [
+            {"id": "a", "summary": "A", "creator": {"email": "me@example.com"},
+             "start": {"date": "2019-08-13"}, "end": {"date": "2019-08-14"}},
+            {"id": "b", "summary": "B", "creator": {"email": "me@example.com"},
+             "start": {"date": "2019-08-13"}, "end": {"date": "2019-08-14"}}]}"#,
+        );
+        synced.update("primary", &date, true, &full).expect("full");
+        assert_eq!(synced.sync_token("primary", &date).map(|t| t.0), Some("one".to_string()));
+        assert!(synced.sync_token("primary", &date.with_day(14).unwrap()).is_none());
+
+        let changes = response(
+            r#"{"nextSyncToken": "two", "items": [
+            {"id": "a", "status": "cancelled"},
+            {"id": "b", "summary": "B", "creator": {"email": "me@example.com"},
+             "start": {"date": "2019-08-15"}, "end": {"date": "2019-08-16"}},
+            {"id": "c", "summary": "C", "creator": {"email": "me@example.com"},
+             "start": {"date": "2019-08-12"}, "end": {"date": "2019-08-14"}}]}"#,
+        );
+        synced.update("primary", &date, false, &changes).expect("changes");
+        assert_eq!(synced.sync_token("primary", &date).map(|t| t.0), Some("two".to_string()));
+
+        let apps = synced
+            .appointments(&date, |_| Some('H'))
+            .expect("appointments")
+            .finalise();
+        let events = apps.events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].summary, "C");
+        assert_eq!(events[0].tag, Some('H'));
+        assert_eq!(apps.email().map(|e| e.0), Some("me@example.com".to_string()));
+    }
+}
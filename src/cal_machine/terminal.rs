@@ -0,0 +1,176 @@
+/*
+Copyright [2019] [Kieran White]
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Commands run from a terminal, rather than by the machine driving
+//! the display.
+
+use crate::{
+    cal_machine::{
+        evs::{Appointments, AppsReadonly, DisplayableOccasion, Email},
+        retriever::*,
+        worker, Authenticators, Error, Persisted, RefreshToken, ACCESS_DENIED, HTTP_ERROR,
+        REFRESH_TOKEN_FILE, UNRECOGNISED_TOKEN_TYPE,
+    },
+    settings::Settings,
+};
+use chrono::prelude::*;
+use reqwest::StatusCode;
+use std::{path::Path, thread, time::Duration};
+
+const DATE_FORMAT: &str = "%a %b %e %Y";
+const EXPIRY_FORMAT: &str = "%H:%M";
+
+/// A command that couldn't be completed, for the reason given.
+#[derive(Debug)]
+pub struct CommandError(pub String);
+
+fn command_error(msg: String) -> Error {
+    Error::Command(CommandError(msg))
+}
+
+/// Asks Google for a user code, prints it and then waits for the user
+/// to grant access to their calendars before passing the refresh
+/// token to saver.
+pub fn authorise(
+    var_dir: &Path,
+    saver: impl FnOnce(Persisted) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let client = ClientCredentials::load(&var_dir.join(CLIENT_SECRET_FILE))?;
+    let retriever = EventRetriever::new(Endpoints::from_env()?);
+
+    let mut resp = retriever.retrieve_dev_and_code(&client)?;
+    let status = resp.status();
+    if status != StatusCode::OK {
+        let body: DeviceUserCodeErrorResponse = resp.json()?;
+        return Err(command_error(format!(
+            "{}: {}, {}",
+            HTTP_ERROR,
+            status.as_u16(),
+            body.error_code
+        )));
+    }
+    let codes: DeviceUserCodeResponse = resp.json()?;
+    let expiry = Local::now() + chrono::Duration::seconds(codes.expires_in);
+    println!(
+        "Visit {} and enter the code {} before {}",
+        codes.verification_url,
+        codes.user_code,
+        expiry.format(EXPIRY_FORMAT)
+    );
+
+    let mut delay_s = u64::from(codes.interval);
+    loop {
+        thread::sleep(Duration::from_secs(delay_s));
+        let mut resp = retriever.poll(&client, &codes.device_code)?;
+        let status = resp.status();
+        if status == StatusCode::OK {
+            let tokens: PollResponse = resp.json()?;
+            if tokens.token_type != TOKEN_TYPE {
+                return Err(command_error(format!(
+                    "{}: {}",
+                    UNRECOGNISED_TOKEN_TYPE, tokens.token_type
+                )));
+            }
+            let auth: Authenticators = tokens.into();
+            saver(Persisted::RefreshToken(&auth.refresh_token))?;
+            println!("Access granted");
+            return Ok(());
+        }
+
+        let body: PollErrorResponse = resp.json()?;
+        match status {
+            StatusCode::BAD_REQUEST | StatusCode::PRECONDITION_REQUIRED
+                if body.error == AUTHORISATION_PENDING_ERROR => {}
+            StatusCode::TOO_MANY_REQUESTS if body.error == POLLING_TOO_FREQUENTLY_ERROR => {
+                delay_s *= 2
+            }
+            StatusCode::FORBIDDEN if body.error == ACCESS_DENIED_ERROR => {
+                return Err(command_error(ACCESS_DENIED.to_string()))
+            }
+            _otherwise => {
+                return Err(command_error(format!(
+                    "{}: {}, {}, {}",
+                    HTTP_ERROR,
+                    status.as_u16(),
+                    body.error,
+                    body.error_description
+                )))
+            }
+        }
+    }
+}
+
+fn status_error(status: StatusCode) -> Error {
+    command_error(format!("{}: {}", HTTP_ERROR, status.as_u16()))
+}
+
+/// Reads the events of every calendar on date, Google's included if
+/// access has been granted.
+pub fn fetch(settings: &Settings, date: DateTime<Local>) -> Result<AppsReadonly, Error> {
+    let mut calendars = settings.calendars()?;
+    let mut events = Appointments::new();
+
+    if calendars.google {
+        let client = ClientCredentials::load(&settings.var_dir.join(CLIENT_SECRET_FILE))?;
+        let refresh_token = RefreshToken::load(&settings.var_dir.join(REFRESH_TOKEN_FILE))?
+            .ok_or_else(|| {
+                command_error("Google calendars haven't been authorised yet".to_string())
+            })?;
+        let retriever = EventRetriever::new(Endpoints::from_env()?);
+
+        let mut resp = retriever.refresh(&client, &refresh_token.0)?;
+        if resp.status() != StatusCode::OK {
+            return Err(status_error(resp.status()));
+        }
+        let tokens: RefreshResponse = resp.json()?;
+        if tokens.token_type != TOKEN_TYPE {
+            return Err(command_error(format!(
+                "{}: {}",
+                UNRECOGNISED_TOKEN_TYPE, tokens.token_type
+            )));
+        }
+        let auth: Authenticators = (refresh_token, tokens).into();
+
+        let bearer = auth.bearer();
+        let ids = worker::calendar_ids(&retriever, &bearer, &calendars.google_calendars)?
+            .map_err(status_error)?;
+        let day = worker::read_day(&retriever, &bearer, &ids, &date, calendars.google_page_size)?
+            .map_err(status_error)?;
+        for (calendar_id, items) in day {
+            events.add(&items, calendars.tag(&calendar_id))?;
+        }
+    }
+
+    calendars.read_day(&date, &mut events);
+    Ok(events.finalise())
+}
+
+/// Prints a day of events, one per line, in the order they're shown on
+/// the display.
+pub fn print_day(date: &DateTime<Local>, apps: &AppsReadonly) {
+    println!("{}", date.format(DATE_FORMAT));
+    if let Some(Email(email)) = apps.email() {
+        println!("{}", email);
+    }
+    let mut events = apps.events();
+    events.sort();
+    for event in events {
+        match event.tag() {
+            Some(tag) => println!("{} {} {}", tag, event.period(), event.description()),
+            None => println!("{} {}", event.period(), event.description()),
+        }
+    }
+}
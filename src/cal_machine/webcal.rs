@@ -0,0 +1,185 @@
+/*
+Copyright [2019] [Kieran White]
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::{
+    cal_machine::{evs::Event, ical},
+    err,
+};
+use chrono::prelude::*;
+use log::trace;
+use reqwest::{
+    self,
+    header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    Client, Response, StatusCode,
+};
+
+const WEBCAL_SCHEME: &str = "webcal://";
+const HTTPS_SCHEME: &str = "https://";
+
+#[derive(Debug)]
+pub struct StatusError(pub StatusCode);
+
+err!(Error {
+    Reqwest(reqwest::Error),
+    Status(StatusError)
+});
+
+impl Error {
+    pub fn message(&self) -> String {
+        match self {
+            Error::Reqwest(err) => format!("HTTP error: {}", err),
+            Error::Status(StatusError(status)) => format!("HTTP status: {}", status),
+        }
+    }
+}
+
+/// A read-only calendar published as an iCalendar feed. The feed is
+/// checked once per refresh and only downloaded again when the server
+/// reports that it has changed. Each day in between is read from the
+/// copy already downloaded.
+pub struct Webcal {
+    client: Client,
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    calendar: Option<String>,
+    stale: bool,
+}
+
+/// webcal:// is the same as https:// but tells a browser to open the
+/// link in a calendar application.
+fn https_url(url: &str) -> String {
+    match url.strip_prefix(WEBCAL_SCHEME) {
+        Some(rest) => format!("{}{}", HTTPS_SCHEME, rest),
+        None => url.to_string(),
+    }
+}
+
+fn header(resp: &Response, name: &reqwest::header::HeaderName) -> Option<String> {
+    resp.headers()
+        .get(name)
+        .and_then(|val| val.to_str().ok())
+        .map(|val| val.to_string())
+}
+
+impl Webcal {
+    pub fn new(url: &str) -> Webcal {
+        Webcal {
+            client: Client::new(),
+            url: https_url(url),
+            etag: None,
+            last_modified: None,
+            calendar: None,
+            stale: true,
+        }
+    }
+
+    pub fn refresh(&mut self) {
+        self.stale = true;
+    }
+
+    fn fetch(&mut self) -> Result<&str, Error> {
+        if !self.stale && self.calendar.is_some() {
+            return Ok(self.calendar.as_deref().unwrap_or(""));
+        }
+        let mut request = self.client.get(&self.url);
+        if self.calendar.is_some() {
+            if let Some(etag) = &self.etag {
+                request = request.header(IF_NONE_MATCH, etag.as_str());
+            }
+            if let Some(last_modified) = &self.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified.as_str());
+            }
+        }
+        trace!("webcal request: {:?}", request);
+
+        let mut resp = request.send()?;
+        match resp.status() {
+            StatusCode::NOT_MODIFIED if self.calendar.is_some() => {
+                trace!("{} not modified", self.url);
+            }
+            StatusCode::OK => {
+                self.etag = header(&resp, &ETAG);
+                self.last_modified = header(&resp, &LAST_MODIFIED);
+                self.calendar = Some(resp.text()?);
+            }
+            other => return Err(StatusError(other).into()),
+        }
+        self.stale = false;
+        Ok(self.calendar.as_deref().unwrap_or(""))
+    }
+
+    pub fn read(
+        &mut self,
+        min_time: &DateTime<Local>,
+        max_time: &DateTime<Local>,
+    ) -> Result<Vec<Event>, Error> {
+        Ok(ical::events_between(self.fetch()?, min_time, max_time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cal_machine::{
+        mock_google::{Canned, MockGoogle},
+        webcal::Webcal,
+    };
+    use chrono::prelude::*;
+
+    const FEED_PATH: &str = "/feed.ics";
+    const FEED: &str = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nSUMMARY:Bins\r\nDTSTART:20261017T090000\r\nDTEND:20261017T093000\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+
+    #[test]
+    fn fetched_once_per_refresh() {
+        let etag = "\"feed-1\"";
+        let last_modified = "Sat, 17 Oct 2026 08:00:00 GMT";
+        let server = MockGoogle::start(vec![(
+            FEED_PATH,
+            vec![
+                Canned::new(200, FEED)
+                    .with_header("ETag", etag)
+                    .with_header("Last-Modified", last_modified),
+                Canned::new(304, ""),
+            ],
+        )]);
+        let mut feed = Webcal::new(&format!("{}{}", server.url(), FEED_PATH));
+        let day = Local.ymd(2026, 10, 17).and_hms(0, 0, 0);
+        let read = |feed: &mut Webcal, offset| {
+            let start = day + chrono::Duration::days(offset);
+            feed.read(&start, &(start + chrono::Duration::days(1)))
+                .expect("feed read")
+                .len()
+        };
+
+        assert_eq!(read(&mut feed, 0), 1);
+        assert_eq!(read(&mut feed, 1), 0);
+        assert_eq!(server.requests().lock().expect("requests").len(), 1);
+        let first = server.headers().lock().expect("headers")[0].clone();
+        assert_eq!(first.get("if-none-match"), None);
+        assert_eq!(first.get("if-modified-since"), None);
+
+        //the unchanged feed is still read from the copy downloaded
+        feed.refresh();
+        assert_eq!(read(&mut feed, 0), 1);
+        assert_eq!(server.requests().lock().expect("requests").len(), 2);
+        let second = server.headers().lock().expect("headers")[1].clone();
+        assert_eq!(second.get("if-none-match").map(String::as_str), Some(etag));
+        assert_eq!(
+            second.get("if-modified-since").map(String::as_str),
+            Some(last_modified)
+        );
+    }
+}
@@ -0,0 +1,284 @@
+/*
+Copyright [2019] [Kieran White]
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::cal_machine::{
+    evs::Event,
+    retriever::{
+        self, CalendarListResponse, CalendarPage, ClientCredentials, DeviceUserCodeErrorResponse,
+        DeviceUserCodeResponse, Endpoints, EventRetriever, EventsResponse, PageToken,
+        PollErrorResponse, PollResponse, RefreshResponse, CANCELLED_STATUS,
+    },
+    sources::{GoogleCalendars, Sources},
+};
+use chrono::prelude::*;
+use log::trace;
+use reqwest::StatusCode;
+use std::{
+    sync::mpsc::{self, Receiver, Sender, TryRecvError},
+    thread,
+};
+
+/// A request to be made off the thread that polls the buttons. The
+/// Google calendar reads each carry the bearer token to authorise them
+/// with. A day is read from the other sources too, refreshing them
+/// first when asked to.
+pub enum Job {
+    DeviceCode(ClientCredentials),
+    Poll(ClientCredentials, String),
+    Refresh(ClientCredentials, String),
+    CalendarIds(String, GoogleCalendars),
+    Page(String, DateTime<Local>, CalendarPage, u32),
+    Day(String, Vec<String>, DateTime<Local>, u32),
+    /// Replaces the other sources. There's no reply.
+    Sources(Sources),
+    Local(DateTime<Local>, bool),
+}
+
+pub enum CodeOutcome {
+    Codes(DeviceUserCodeResponse),
+    Refused(StatusCode, DeviceUserCodeErrorResponse),
+}
+
+pub enum PollOutcome {
+    Tokens(PollResponse),
+    Refused(StatusCode, PollErrorResponse),
+    Unreadable(StatusCode, reqwest::Error),
+}
+
+pub enum PageOutcome {
+    Events(EventsResponse),
+    Gone,
+    Failed(StatusCode),
+}
+
+pub type CalendarEvents = (String, Vec<retriever::Event>);
+
+pub enum Reply {
+    DeviceCode(reqwest::Result<CodeOutcome>),
+    Poll(reqwest::Result<PollOutcome>),
+    Refresh(reqwest::Result<Result<RefreshResponse, StatusCode>>),
+    CalendarIds(reqwest::Result<Result<Vec<String>, StatusCode>>),
+    Page(CalendarPage, reqwest::Result<PageOutcome>),
+    Day(
+        DateTime<Local>,
+        reqwest::Result<Result<Vec<CalendarEvents>, StatusCode>>,
+        Vec<Event>,
+    ),
+    Local(DateTime<Local>, Vec<Event>),
+}
+
+/// Runs jobs one at a time, in the order they were submitted, so
+/// replies arrive in that order too.
+pub struct Worker {
+    jobs: Sender<Job>,
+    replies: Receiver<Reply>,
+}
+
+impl Worker {
+    pub fn spawn(endpoints: Endpoints) -> Worker {
+        let (jobs, job_rx) = mpsc::channel::<Job>();
+        let (reply_tx, replies) = mpsc::channel();
+        thread::spawn(move || {
+            let retriever = EventRetriever::new(endpoints);
+            let mut sources = Sources::default();
+            for job in job_rx {
+                let reply = match perform(&retriever, &mut sources, job) {
+                    Some(reply) => reply,
+                    None => continue,
+                };
+                if reply_tx.send(reply).is_err() {
+                    break;
+                }
+            }
+            trace!("network worker finished");
+        });
+
+        Worker { jobs, replies }
+    }
+
+    pub fn submit(&self, job: Job) {
+        self.jobs
+            .send(job)
+            .expect("submit(). network worker has stopped");
+    }
+
+    /// The reply to the earliest outstanding job if it has finished.
+    pub fn reply(&self) -> Option<Reply> {
+        match self.replies.try_recv() {
+            Ok(reply) => Some(reply),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => panic!("reply(). network worker has stopped"),
+        }
+    }
+}
+
+fn perform(retriever: &EventRetriever, sources: &mut Sources, job: Job) -> Option<Reply> {
+    Some(match job {
+        Job::DeviceCode(client) => Reply::DeviceCode(device_code(retriever, &client)),
+        Job::Poll(client, device_code) => Reply::Poll(poll(retriever, &client, &device_code)),
+        Job::Refresh(client, refresh_token) => {
+            Reply::Refresh(refresh(retriever, &client, &refresh_token))
+        }
+        Job::CalendarIds(bearer, google_calendars) => {
+            Reply::CalendarIds(calendar_ids(retriever, &bearer, &google_calendars))
+        }
+        Job::Page(bearer, date, page, page_size) => {
+            let outcome = read_page(retriever, &bearer, &date, &page, page_size);
+            Reply::Page(page, outcome)
+        }
+        Job::Day(bearer, calendar_ids, date, page_size) => {
+            let day = read_day(retriever, &bearer, &calendar_ids, &date, page_size);
+            Reply::Day(date, day, sources.read_day(&date))
+        }
+        Job::Sources(replacement) => {
+            *sources = replacement;
+            return None;
+        }
+        Job::Local(date, refresh) => {
+            if refresh {
+                sources.refresh();
+            }
+            Reply::Local(date, sources.read_day(&date))
+        }
+    })
+}
+
+fn device_code(
+    retriever: &EventRetriever,
+    client: &ClientCredentials,
+) -> reqwest::Result<CodeOutcome> {
+    let mut resp = retriever.retrieve_dev_and_code(client)?;
+    match resp.status() {
+        StatusCode::OK => Ok(CodeOutcome::Codes(resp.json()?)),
+        status => Ok(CodeOutcome::Refused(status, resp.json()?)),
+    }
+}
+
+fn poll(
+    retriever: &EventRetriever,
+    client: &ClientCredentials,
+    device_code: &str,
+) -> reqwest::Result<PollOutcome> {
+    let mut resp = retriever.poll(client, device_code)?;
+    match resp.status() {
+        StatusCode::OK => Ok(PollOutcome::Tokens(resp.json()?)),
+        status => match resp.json() {
+            Ok(body) => Ok(PollOutcome::Refused(status, body)),
+            Err(err) => Ok(PollOutcome::Unreadable(status, err)),
+        },
+    }
+}
+
+fn refresh(
+    retriever: &EventRetriever,
+    client: &ClientCredentials,
+    refresh_token: &str,
+) -> reqwest::Result<Result<RefreshResponse, StatusCode>> {
+    let mut resp = retriever.refresh(client, refresh_token)?;
+    match resp.status() {
+        StatusCode::OK => Ok(Ok(resp.json()?)),
+        status => Ok(Err(status)),
+    }
+}
+
+fn day_end(date: &DateTime<Local>) -> DateTime<Local> {
+    *date + chrono::Duration::days(1) - chrono::Duration::seconds(1)
+}
+
+pub fn calendar_ids(
+    retriever: &EventRetriever,
+    bearer: &str,
+    google_calendars: &GoogleCalendars,
+) -> reqwest::Result<Result<Vec<String>, StatusCode>> {
+    let mut ids = match google_calendars {
+        GoogleCalendars::Listed(ids) => return Ok(Ok(ids.clone())),
+        GoogleCalendars::Selected => Vec::new(),
+    };
+
+    let mut page_token = None;
+    loop {
+        let mut resp = retriever.list_calendars(bearer, &page_token)?;
+        if resp.status() != StatusCode::OK {
+            println!("Calendar list is next... {:?}", resp.text()?);
+            return Ok(Err(resp.status()));
+        }
+
+        let list: CalendarListResponse = resp.json()?;
+        ids.extend(
+            list.items
+                .into_iter()
+                .filter(|entry| entry.selected == Some(true))
+                .map(|entry| entry.id),
+        );
+        match list.next_page_token {
+            None => return Ok(Ok(ids)),
+            Some(next_page) => page_token = Some(PageToken(next_page)),
+        }
+    }
+}
+
+fn read_page(
+    retriever: &EventRetriever,
+    bearer: &str,
+    date: &DateTime<Local>,
+    page: &CalendarPage,
+    page_size: u32,
+) -> reqwest::Result<PageOutcome> {
+    let mut resp = retriever.read(bearer, date, &day_end(date), page, page_size)?;
+    match resp.status() {
+        StatusCode::OK => Ok(PageOutcome::Events(resp.json()?)),
+        StatusCode::GONE => Ok(PageOutcome::Gone),
+        status => {
+            println!("Event Headers: {:#?}", resp.headers());
+            println!("Event is next... {:?}", resp.text()?);
+            Ok(PageOutcome::Failed(status))
+        }
+    }
+}
+
+/// Every page of each calendar's events on date, read in full.
+pub fn read_day(
+    retriever: &EventRetriever,
+    bearer: &str,
+    calendar_ids: &[String],
+    date: &DateTime<Local>,
+    page_size: u32,
+) -> reqwest::Result<Result<Vec<CalendarEvents>, StatusCode>> {
+    let mut day = Vec::with_capacity(calendar_ids.len());
+    for calendar_id in calendar_ids {
+        let mut page = CalendarPage::first(calendar_id.clone(), None);
+        let mut items = Vec::new();
+        loop {
+            let events_resp = match read_page(retriever, bearer, date, &page, page_size)? {
+                PageOutcome::Events(events_resp) => events_resp,
+                PageOutcome::Gone => return Ok(Err(StatusCode::GONE)),
+                PageOutcome::Failed(status) => return Ok(Err(status)),
+            };
+            items.extend(
+                events_resp
+                    .items
+                    .into_iter()
+                    .filter(|item| item.status.as_deref() != Some(CANCELLED_STATUS)),
+            );
+            match events_resp.next_page_token {
+                None => break,
+                Some(next_page) => page.page_token = Some(PageToken(next_page)),
+            }
+        }
+        day.push((calendar_id.clone(), items));
+    }
+    Ok(Ok(day))
+}
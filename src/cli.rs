@@ -0,0 +1,195 @@
+/*
+Copyright [2019] [Kieran White]
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::err;
+use chrono::{format::ParseError, NaiveDate};
+use log::LevelFilter;
+use std::{path::PathBuf, str::FromStr};
+
+pub const USAGE: &str = "\
+Usage: calendar_mirror [OPTIONS] [COMMAND]
+
+Commands:
+  run                Show the calendars on the display (the default)
+  install            Install this binary and start its systemd unit
+  uninstall          Stop the systemd unit and remove the installation
+  auth               Authorise access to Google calendars from this terminal
+  fetch --date DATE  Print the events on DATE, given as YYYY-MM-DD
+  render-stms        Write graphs of the state machines to docs/
+  check-config       Check that the settings and calendars can be used
+
+Options:
+  --var-dir DIR      Where the settings and credentials are kept
+                     (default: $CALENDAR_MIRROR_VAR or .)
+  --device DEV       Block device to mount at the var dir
+                     (default: $CALENDAR_MIRROR_DEV)
+  --log-level LEVEL  off, error, warn, info, debug or trace
+                     (default: $RUST_LOG)
+  --help             Print this message
+";
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// The command line can't be understood, for the reason given.
+#[derive(Debug)]
+pub struct UsageError(pub String);
+
+err!(Error {
+    Usage(UsageError),
+    Date(ParseError)
+});
+
+impl Error {
+    pub fn message(&self) -> String {
+        match self {
+            Error::Usage(UsageError(msg)) => msg.clone(),
+            Error::Date(err) => format!("--date must be given as YYYY-MM-DD: {}", err),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Run,
+    Install,
+    Uninstall,
+    Auth,
+    Fetch(NaiveDate),
+    RenderStms,
+    CheckConfig,
+    Help,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Options {
+    pub var_dir: Option<PathBuf>,
+    pub device: Option<PathBuf>,
+    pub log_level: Option<LevelFilter>,
+    pub command: Command,
+}
+
+fn usage(msg: String) -> Error {
+    Error::Usage(UsageError(msg))
+}
+
+fn value(args: &mut impl Iterator<Item = String>, option: &str) -> Result<String, Error> {
+    args.next()
+        .ok_or_else(|| usage(format!("{} needs a value", option)))
+}
+
+impl Options {
+    /// Parses args, which start with the name of the program, as
+    /// returned by env::args().
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Options, Error> {
+        let mut args = args.into_iter().skip(1);
+        let mut var_dir = None;
+        let mut device = None;
+        let mut log_level = None;
+        let mut date = None;
+        let mut command: Option<String> = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--var-dir" => var_dir = Some(PathBuf::from(value(&mut args, &arg)?)),
+                "--device" => device = Some(PathBuf::from(value(&mut args, &arg)?)),
+                "--log-level" => {
+                    let level = value(&mut args, &arg)?;
+                    log_level = Some(
+                        LevelFilter::from_str(&level)
+                            .map_err(|_| usage(format!("unknown log level: {}", level)))?,
+                    );
+                }
+                "--date" => {
+                    date = Some(NaiveDate::parse_from_str(
+                        &value(&mut args, &arg)?,
+                        DATE_FORMAT,
+                    )?)
+                }
+                "--help" | "-h" => command = Some("help".to_string()),
+                //the flags used before there were subcommands
+                "--install" => command = Some("install".to_string()),
+                "--uninstall" => command = Some("uninstall".to_string()),
+                name if !name.starts_with('-') && command.is_none() => {
+                    command = Some(name.to_string())
+                }
+                other => return Err(usage(format!("unexpected argument: {}", other))),
+            }
+        }
+
+        let command = match (command.as_deref().unwrap_or("run"), date) {
+            ("help", _) => Command::Help,
+            ("fetch", Some(date)) => Command::Fetch(date),
+            ("fetch", None) => return Err(usage("fetch needs --date".to_string())),
+            (_, Some(_)) => return Err(usage("--date is only used by fetch".to_string())),
+            ("run", None) => Command::Run,
+            ("install", None) => Command::Install,
+            ("uninstall", None) => Command::Uninstall,
+            ("auth", None) => Command::Auth,
+            ("render-stms", None) => Command::RenderStms,
+            ("check-config", None) => Command::CheckConfig,
+            (other, None) => return Err(usage(format!("unknown command: {}", other))),
+        };
+        Ok(Options {
+            var_dir,
+            device,
+            log_level,
+            command,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::{Command, Options};
+    use chrono::NaiveDate;
+    use log::LevelFilter;
+    use std::path::PathBuf;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        let args = ["calendar_mirror"].iter().chain(args.iter());
+        Options::parse(args.map(|arg| arg.to_string())).map_err(|err| err.message())
+    }
+
+    #[test]
+    fn commands_and_options() {
+        assert_eq!(parse(&[]).expect("no args").command, Command::Run);
+        assert_eq!(parse(&["--install"]).expect("legacy").command, Command::Install);
+
+        let options = parse(&[
+            "--var-dir",
+            "/var/opt/calendar_mirror",
+            "fetch",
+            "--date",
+            "2019-11-05",
+            "--log-level",
+            "debug",
+        ])
+        .expect("fetch");
+        assert_eq!(options.command, Command::Fetch(NaiveDate::from_ymd(2019, 11, 5)));
+        assert_eq!(options.var_dir, Some(PathBuf::from("/var/opt/calendar_mirror")));
+        assert_eq!(options.log_level, Some(LevelFilter::Debug));
+    }
+
+    #[test]
+    fn misuse() {
+        assert!(parse(&["fetch"]).is_err());
+        assert!(parse(&["auth", "--date", "2019-11-05"]).is_err());
+        assert!(parse(&["fetch", "--date", "5/11/2019"]).is_err());
+        assert!(parse(&["--log-level", "loud"]).is_err());
+        assert!(parse(&["frobnicate"]).is_err());
+        assert!(parse(&["run", "auth"]).is_err());
+    }
+}
@@ -0,0 +1,167 @@
+/*
+Copyright [2019] [Kieran White]
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::display::{
+    panel::{Panel, Rotation},
+    raster::{FrameBuffer, TextCanvas},
+    DisplayBackend, Error, Operation,
+};
+use log::trace;
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    slice::Iter,
+};
+
+pub const DEFAULT_EPD_DIR: &str = "/dev/epd";
+
+const DISPLAY_FILE: &str = "display";
+const COMMAND_FILE: &str = "command";
+const PANEL_FILE: &str = "panel";
+
+const UPDATE_COMMAND: &[u8] = b"U";
+const PARTIAL_COMMAND: &[u8] = b"P";
+const CLEAR_COMMAND: &[u8] = b"C";
+
+/// Reads the panel dimensions in pixels reported by the epd fuse
+/// driver, for example "EPD 2.7 264x176 COG 2 FILM 231".
+pub fn panel_dims(epd_dir: &Path) -> Option<(u32, u32)> {
+    let description = fs::read_to_string(epd_dir.join(PANEL_FILE)).ok()?;
+    description.split_whitespace().find_map(|word| {
+        let mut dims = word.split('x');
+        match (dims.next(), dims.next(), dims.next()) {
+            (Some(width), Some(height), None) => {
+                Some((width.parse().ok()?, height.parse().ok()?))
+            }
+            _ => None,
+        }
+    })
+}
+
+/// Drives a PaPiRus display directly through the files exposed by
+/// the epd fuse driver, normally mounted at /dev/epd. Text is drawn
+/// into a framebuffer which is written to the display file, followed
+/// by an update command.
+pub struct Epd {
+    canvas: TextCanvas,
+    display: PathBuf,
+    command: PathBuf,
+    panel: Option<Panel>,
+}
+
+impl Epd {
+    /// The fallback panel, normally the one in the settings, gives the
+    /// size when the driver doesn't report one.
+    pub fn new(epd_dir: &Path, fallback: Panel) -> Epd {
+        let dims = panel_dims(epd_dir);
+        let (width, height) = dims.unwrap_or_else(|| fallback.pixels());
+        trace!("epd panel at {:?} is {}x{}", epd_dir, width, height);
+        Epd {
+            canvas: TextCanvas::new(width, height),
+            display: epd_dir.join(DISPLAY_FILE),
+            command: epd_dir.join(COMMAND_FILE),
+            panel: dims.map_or(Some(fallback), Panel::from_pixels),
+        }
+    }
+
+    //The fuse driver doesn't support creating or truncating its files
+    fn write_to(path: &Path, data: &[u8]) -> Result<(), Error> {
+        let mut file = OpenOptions::new().write(true).open(path)?;
+        file.write_all(data)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    fn show(frame: &FrameBuffer, display: &Path, command: &Path, partial: bool) -> Result<(), Error> {
+        Epd::write_to(display, frame.bits())?;
+        Epd::write_to(
+            command,
+            if partial {
+                PARTIAL_COMMAND
+            } else {
+                UPDATE_COMMAND
+            },
+        )
+    }
+}
+
+impl DisplayBackend for Epd {
+    //Writes to the command file block until the panel has been
+    //updated so every send is synchronous
+    fn send(&mut self, els: Iter<Operation>, _sync: bool) -> Result<(), Error> {
+        for el in els {
+            if let Operation::Clear = el {
+                Epd::write_to(&self.command, CLEAR_COMMAND)?;
+            }
+            if let Some((frame, partial)) = self.canvas.apply(el) {
+                Epd::show(frame, &self.display, &self.command, partial)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn panel(&self) -> Option<Panel> {
+        self.panel
+    }
+
+    fn rotate(&mut self, rotation: Rotation) {
+        self.canvas.set_rotation(rotation);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        display::{
+            epd::{panel_dims, Epd},
+            panel::Panel,
+            DisplayBackend, Operation, PartialUpdate, Pos,
+        },
+        temp_dir::TempDir,
+    };
+    use std::fs::{self, File};
+
+    #[test]
+    fn write_all() {
+        let dir = TempDir::new("epd");
+        File::create(dir.join("display")).expect("display file");
+        File::create(dir.join("command")).expect("command file");
+        assert_eq!(panel_dims(&dir), None);
+        let mut unread = Epd::new(&dir, Panel::Small);
+        assert_eq!(unread.panel(), Some(Panel::Small));
+        unread
+            .send([Operation::WriteAll(PartialUpdate(false))].iter(), false)
+            .expect("send without a panel file");
+        assert_eq!(fs::read(dir.join("display")).expect("display").len(), 128 / 8 * 96);
+        fs::write(dir.join("panel"), "EPD 1.44 128x96 COG 2 FILM 231\n").expect("panel file");
+        assert_eq!(panel_dims(&dir), Some((128, 96)));
+
+        let mut epd = Epd::new(&dir, Panel::Large);
+        assert_eq!(epd.panel(), Some(Panel::Small));
+        let ops = [
+            Operation::Clear,
+            Operation::AddText("|".to_string(), Pos(0, 0), 10, "id".to_string()),
+            Operation::WriteAll(PartialUpdate(true)),
+        ];
+        epd.send(ops.iter(), false).expect("send");
+
+        let display = fs::read(dir.join("display")).expect("display contents");
+        assert_eq!(display.len(), 128 / 8 * 96);
+        assert_eq!(display[0], 0x20); //the bar is the third of six columns
+        assert_eq!(fs::read(dir.join("command")).expect("command"), b"P");
+    }
+}
@@ -0,0 +1,88 @@
+/*
+Copyright [2019] [Kieran White]
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use serde::Deserialize;
+
+/// The sizes of PaPiRus panel, named after their diagonals in inches.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+pub enum Panel {
+    #[serde(rename = "1.44")]
+    Small,
+    #[serde(rename = "1.9")]
+    Medium,
+    #[serde(rename = "2.0")]
+    Wide,
+    #[default]
+    #[serde(rename = "2.7")]
+    Large,
+}
+
+impl Panel {
+    /// Width and height in pixels.
+    pub fn pixels(self) -> (u32, u32) {
+        match self {
+            Panel::Small => (128, 96),
+            Panel::Medium => (144, 128),
+            Panel::Wide => (200, 96),
+            Panel::Large => (264, 176),
+        }
+    }
+
+    pub fn from_pixels(pixels: (u32, u32)) -> Option<Panel> {
+        [Panel::Small, Panel::Medium, Panel::Wide, Panel::Large]
+            .iter()
+            .find(|panel| panel.pixels() == pixels)
+            .copied()
+    }
+}
+
+/// How far the whole screen is turned clockwise, for panels mounted
+/// other than in landscape.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Rotation {
+    #[default]
+    None,
+    Quarter,
+    Half,
+    ThreeQuarters,
+}
+
+impl Rotation {
+    pub fn from_degrees(degrees: u32) -> Option<Rotation> {
+        match degrees {
+            0 => Some(Rotation::None),
+            90 => Some(Rotation::Quarter),
+            180 => Some(Rotation::Half),
+            270 => Some(Rotation::ThreeQuarters),
+            _ => None,
+        }
+    }
+
+    /// Whether a landscape panel is shown in portrait.
+    pub fn portrait(self) -> bool {
+        self == Rotation::Quarter || self == Rotation::ThreeQuarters
+    }
+
+    /// The width and height of the screen as drawn, before it's turned
+    /// to fit a panel of width by height pixels.
+    pub fn unturned(self, (width, height): (u32, u32)) -> (u32, u32) {
+        if self.portrait() {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+}
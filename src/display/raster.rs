@@ -0,0 +1,488 @@
+/*
+Copyright [2019] [Kieran White]
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::display::{panel::Rotation, Operation, PartialUpdate, Pos};
+use std::io::{self, Write};
+
+const FIRST_GLYPH: char = ' ';
+const LAST_GLYPH: char = '~';
+const UNKNOWN_GLYPH: char = '?';
+const GLYPH_COLS: u32 = 6; //5 columns of glyph plus one of spacing
+const GLYPH_ROWS: u32 = 8; //7 rows of glyph plus one of spacing
+
+// 5x7 glyphs for printable ASCII. Each byte is a column, least
+// significant bit at the top.
+const FONT: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5F, 0x00, 0x00], // !
+    [0x00, 0x07, 0x00, 0x07, 0x00], // "
+    [0x14, 0x7F, 0x14, 0x7F, 0x14], // #
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12], // $
+    [0x23, 0x13, 0x08, 0x64, 0x62], // %
+    [0x36, 0x49, 0x55, 0x22, 0x50], // &
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '
+    [0x00, 0x1C, 0x22, 0x41, 0x00], // (
+    [0x00, 0x41, 0x22, 0x1C, 0x00], // )
+    [0x08, 0x2A, 0x1C, 0x2A, 0x08], // *
+    [0x08, 0x08, 0x3E, 0x08, 0x08], // +
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ,
+    [0x08, 0x08, 0x08, 0x08, 0x08], // -
+    [0x00, 0x60, 0x60, 0x00, 0x00], // .
+    [0x20, 0x10, 0x08, 0x04, 0x02], // /
+    [0x3E, 0x51, 0x49, 0x45, 0x3E], // 0
+    [0x00, 0x42, 0x7F, 0x40, 0x00], // 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], // 2
+    [0x21, 0x41, 0x45, 0x4B, 0x31], // 3
+    [0x18, 0x14, 0x12, 0x7F, 0x10], // 4
+    [0x27, 0x45, 0x45, 0x45, 0x39], // 5
+    [0x3C, 0x4A, 0x49, 0x49, 0x30], // 6
+    [0x01, 0x71, 0x09, 0x05, 0x03], // 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], // 8
+    [0x06, 0x49, 0x49, 0x29, 0x1E], // 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], // :
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ;
+    [0x08, 0x14, 0x22, 0x41, 0x00], // <
+    [0x14, 0x14, 0x14, 0x14, 0x14], // =
+    [0x00, 0x41, 0x22, 0x14, 0x08], // >
+    [0x02, 0x01, 0x51, 0x09, 0x06], // ?
+    [0x32, 0x49, 0x79, 0x41, 0x3E], // @
+    [0x7E, 0x11, 0x11, 0x11, 0x7E], // A
+    [0x7F, 0x49, 0x49, 0x49, 0x36], // B
+    [0x3E, 0x41, 0x41, 0x41, 0x22], // C
+    [0x7F, 0x41, 0x41, 0x22, 0x1C], // D
+    [0x7F, 0x49, 0x49, 0x49, 0x41], // E
+    [0x7F, 0x09, 0x09, 0x01, 0x01], // F
+    [0x3E, 0x41, 0x41, 0x51, 0x32], // G
+    [0x7F, 0x08, 0x08, 0x08, 0x7F], // H
+    [0x00, 0x41, 0x7F, 0x41, 0x00], // I
+    [0x20, 0x40, 0x41, 0x3F, 0x01], // J
+    [0x7F, 0x08, 0x14, 0x22, 0x41], // K
+    [0x7F, 0x40, 0x40, 0x40, 0x40], // L
+    [0x7F, 0x02, 0x04, 0x02, 0x7F], // M
+    [0x7F, 0x04, 0x08, 0x10, 0x7F], // N
+    [0x3E, 0x41, 0x41, 0x41, 0x3E], // O
+    [0x7F, 0x09, 0x09, 0x09, 0x06], // P
+    [0x3E, 0x41, 0x51, 0x21, 0x5E], // Q
+    [0x7F, 0x09, 0x19, 0x29, 0x46], // R
+    [0x46, 0x49, 0x49, 0x49, 0x31], // S
+    [0x01, 0x01, 0x7F, 0x01, 0x01], // T
+    [0x3F, 0x40, 0x40, 0x40, 0x3F], // U
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], // V
+    [0x7F, 0x20, 0x18, 0x20, 0x7F], // W
+    [0x63, 0x14, 0x08, 0x14, 0x63], // X
+    [0x03, 0x04, 0x78, 0x04, 0x03], // Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], // Z
+    [0x00, 0x7F, 0x41, 0x41, 0x00], // [
+    [0x02, 0x04, 0x08, 0x10, 0x20], // \
+    [0x00, 0x41, 0x41, 0x7F, 0x00], // ]
+    [0x04, 0x02, 0x01, 0x02, 0x04], // ^
+    [0x40, 0x40, 0x40, 0x40, 0x40], // _
+    [0x00, 0x01, 0x02, 0x04, 0x00], // `
+    [0x20, 0x54, 0x54, 0x54, 0x78], // a
+    [0x7F, 0x48, 0x44, 0x44, 0x38], // b
+    [0x38, 0x44, 0x44, 0x44, 0x20], // c
+    [0x38, 0x44, 0x44, 0x48, 0x7F], // d
+    [0x38, 0x54, 0x54, 0x54, 0x18], // e
+    [0x08, 0x7E, 0x09, 0x01, 0x02], // f
+    [0x08, 0x54, 0x54, 0x54, 0x3C], // g
+    [0x7F, 0x08, 0x04, 0x04, 0x78], // h
+    [0x00, 0x44, 0x7D, 0x40, 0x00], // i
+    [0x20, 0x40, 0x44, 0x3D, 0x00], // j
+    [0x7F, 0x10, 0x28, 0x44, 0x00], // k
+    [0x00, 0x41, 0x7F, 0x40, 0x00], // l
+    [0x7C, 0x04, 0x18, 0x04, 0x78], // m
+    [0x7C, 0x08, 0x04, 0x04, 0x78], // n
+    [0x38, 0x44, 0x44, 0x44, 0x38], // o
+    [0x7C, 0x14, 0x14, 0x14, 0x08], // p
+    [0x08, 0x14, 0x14, 0x18, 0x7C], // q
+    [0x7C, 0x08, 0x04, 0x04, 0x08], // r
+    [0x48, 0x54, 0x54, 0x54, 0x20], // s
+    [0x04, 0x3F, 0x44, 0x40, 0x20], // t
+    [0x3C, 0x40, 0x40, 0x20, 0x7C], // u
+    [0x1C, 0x20, 0x40, 0x20, 0x1C], // v
+    [0x3C, 0x40, 0x30, 0x40, 0x3C], // w
+    [0x44, 0x28, 0x10, 0x28, 0x44], // x
+    [0x0C, 0x50, 0x50, 0x50, 0x3C], // y
+    [0x44, 0x64, 0x54, 0x4C, 0x44], // z
+    [0x00, 0x08, 0x36, 0x41, 0x00], // {
+    [0x00, 0x00, 0x7F, 0x00, 0x00], // |
+    [0x00, 0x41, 0x36, 0x08, 0x00], // }
+    [0x08, 0x04, 0x08, 0x10, 0x08], // ~
+];
+
+fn glyph(c: char) -> &'static [u8; 5] {
+    let c = if c < FIRST_GLYPH || c > LAST_GLYPH {
+        UNKNOWN_GLYPH
+    } else {
+        c
+    };
+    &FONT[c as usize - FIRST_GLYPH as usize]
+}
+
+/// Width in pixels of a single character cell at the given font
+/// size. The proportions follow those of the monospaced font used by
+/// the PaPiRus python library.
+pub fn cell_width(size: u32) -> u32 {
+    (size * 3 / 5).max(1)
+}
+
+/// Height in pixels of a single line of text at the given font size.
+pub fn line_height(size: u32) -> u32 {
+    size.max(1)
+}
+
+/// A 1-bit image. Pixels are packed 8 to a byte, most significant bit
+/// first, and each row begins on a byte boundary. A set bit is black.
+pub struct FrameBuffer {
+    width: u32,
+    height: u32,
+    bits: Vec<u8>,
+}
+
+impl FrameBuffer {
+    pub fn new(width: u32, height: u32) -> FrameBuffer {
+        let row_bytes = width.div_ceil(8) as usize;
+        FrameBuffer {
+            width,
+            height,
+            bits: vec![0; row_bytes * height as usize],
+        }
+    }
+
+    pub fn row_bytes(&self) -> usize {
+        self.width.div_ceil(8) as usize
+    }
+
+    pub fn bits(&self) -> &[u8] {
+        &self.bits
+    }
+
+    pub fn clear(&mut self) {
+        for byte in self.bits.iter_mut() {
+            *byte = 0;
+        }
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> bool {
+        if x >= self.width || y >= self.height {
+            return false;
+        }
+        let idx = y as usize * self.row_bytes() + (x / 8) as usize;
+        self.bits[idx] & (0x80 >> (x % 8)) != 0
+    }
+
+    pub fn set(&mut self, x: i64, y: i64, black: bool) {
+        if x < 0 || y < 0 || x >= i64::from(self.width) || y >= i64::from(self.height) {
+            return;
+        }
+        let (x, y) = (x as u32, y as u32);
+        let idx = y as usize * self.row_bytes() + (x / 8) as usize;
+        let mask = 0x80 >> (x % 8);
+        if black {
+            self.bits[idx] |= mask;
+        } else {
+            self.bits[idx] &= !mask;
+        }
+    }
+
+    /// Draws text in black with its top left corner at pos. Newlines
+    /// begin a new row of text directly below the first.
+    pub fn draw_text(&mut self, text: &str, pos: &Pos, size: u32) {
+        let cell_w = cell_width(size);
+        let line_h = line_height(size);
+        for (row, line) in text.lines().enumerate() {
+            let top = i64::from(pos.1) + row as i64 * i64::from(line_h);
+            for (col, c) in line.chars().enumerate() {
+                let left = i64::from(pos.0) + col as i64 * i64::from(cell_w);
+                let columns = glyph(c);
+                //sample the glyph at the centre of each pixel in the cell
+                for cy in 0..line_h {
+                    let gy = (2 * cy + 1) * GLYPH_ROWS / (2 * line_h);
+                    for cx in 0..cell_w {
+                        let gx = (2 * cx + 1) * GLYPH_COLS / (2 * cell_w);
+                        if gx < 5 && gy < 7 && columns[gx as usize] & (1 << gy) != 0 {
+                            self.set(left + i64::from(cx), top + i64::from(cy), true);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Writes the image as a binary (P4) portable bitmap.
+    pub fn write_pbm<W: Write>(&self, output: &mut W) -> io::Result<()> {
+        write!(output, "P4\n{} {}\n", self.width, self.height)?;
+        output.write_all(&self.bits)
+    }
+
+    /// Writes the image as a 1-bit greyscale PNG. The image data is
+    /// stored uncompressed, which is plenty for a screen of this size.
+    pub fn write_png<W: Write>(&self, output: &mut W) -> io::Result<()> {
+        const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+        const MAX_STORED_BLOCK: usize = 0xFFFF;
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&self.width.to_be_bytes());
+        header.extend_from_slice(&self.height.to_be_bytes());
+        header.extend_from_slice(&[1, 0, 0, 0, 0]); //bit depth, greyscale, deflate, filter, no interlace
+
+        //PNG greyscale treats a set bit as white so the rows are inverted
+        let mut raw = Vec::with_capacity(self.bits.len() + self.height as usize);
+        for row in self.bits.chunks(self.row_bytes()) {
+            raw.push(0); //no filter
+            raw.extend(row.iter().map(|byte| !byte));
+        }
+
+        let mut zlib = vec![0x78, 0x01];
+        let mut blocks = raw.chunks(MAX_STORED_BLOCK).peekable();
+        while let Some(block) = blocks.next() {
+            let len = block.len() as u16;
+            zlib.push(if blocks.peek().is_none() { 1 } else { 0 });
+            zlib.extend_from_slice(&len.to_le_bytes());
+            zlib.extend_from_slice(&(!len).to_le_bytes());
+            zlib.extend_from_slice(block);
+        }
+        zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+        output.write_all(&SIGNATURE)?;
+        write_png_chunk(output, b"IHDR", &header)?;
+        write_png_chunk(output, b"IDAT", &zlib)?;
+        write_png_chunk(output, b"IEND", &[])
+    }
+}
+
+fn write_png_chunk<W: Write>(output: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    output.write_all(&(data.len() as u32).to_be_bytes())?;
+    output.write_all(kind)?;
+    output.write_all(data)?;
+    let mut crc = crc32_update(0xFFFF_FFFF, kind);
+    crc = crc32_update(crc, data);
+    output.write_all(&(!crc).to_be_bytes())
+}
+
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                0xEDB8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MOD_ADLER: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + u32::from(*byte)) % MOD_ADLER;
+        b = (b + a) % MOD_ADLER;
+    }
+    (b << 16) | a
+}
+
+struct TextItem {
+    id: String,
+    text: String,
+    pos: Pos,
+    size: u32,
+}
+
+/// Maintains the set of text items on screen in the same way as the
+/// PaPiRus library's PapirusTextPos class, rendering them to a
+/// FrameBuffer on request.
+pub struct TextCanvas {
+    items: Vec<TextItem>,
+    frame: FrameBuffer,
+    rotation: Rotation,
+    turned: FrameBuffer,
+}
+
+impl TextCanvas {
+    pub fn new(width: u32, height: u32) -> TextCanvas {
+        TextCanvas {
+            items: Vec::new(),
+            frame: FrameBuffer::new(width, height),
+            rotation: Rotation::None,
+            turned: FrameBuffer::new(width, height),
+        }
+    }
+
+    /// Draws the text turned by rotation from the next WriteAll, when
+    /// the positions of the text are those on the turned screen.
+    pub fn set_rotation(&mut self, rotation: Rotation) {
+        let (width, height) = rotation.unturned((self.turned.width, self.turned.height));
+        self.frame = FrameBuffer::new(width, height);
+        self.rotation = rotation;
+    }
+
+    fn turn(&mut self) -> &FrameBuffer {
+        let (width, height) = (i64::from(self.frame.width), i64::from(self.frame.height));
+        self.turned.clear();
+        for y in 0..height {
+            for x in 0..width {
+                if !self.frame.get(x as u32, y as u32) {
+                    continue;
+                }
+                let (tx, ty) = match self.rotation {
+                    Rotation::None => (x, y),
+                    Rotation::Quarter => (height - 1 - y, x),
+                    Rotation::Half => (width - 1 - x, height - 1 - y),
+                    Rotation::ThreeQuarters => (y, width - 1 - x),
+                };
+                self.turned.set(tx, ty, true);
+            }
+        }
+        &self.turned
+    }
+
+    /// Applies a single operation. If the operation is a WriteAll the
+    /// freshly drawn frame is returned along with the update type.
+    pub fn apply(&mut self, op: &Operation) -> Option<(&FrameBuffer, bool)> {
+        match op {
+            Operation::AddText(text, pos, size, id) => {
+                //as with PapirusTextPos, adding an existing id is a no-op
+                if !self.items.iter().any(|item| &item.id == id) {
+                    self.items.push(TextItem {
+                        id: id.clone(),
+                        text: text.clone(),
+                        pos: Pos(pos.0, pos.1),
+                        size: *size,
+                    });
+                }
+            }
+            Operation::UpdateText(id, text) => {
+                if let Some(item) = self.items.iter_mut().find(|item| &item.id == id) {
+                    item.text = text.clone();
+                }
+            }
+            Operation::RemoveText(id) => {
+                self.items.retain(|item| &item.id != id);
+            }
+            Operation::Clear => {
+                self.items.clear();
+            }
+            Operation::WriteAll(PartialUpdate(partial)) => {
+                self.frame.clear();
+                for item in self.items.iter() {
+                    self.frame.draw_text(&item.text, &item.pos, item.size);
+                }
+                if self.rotation == Rotation::None {
+                    return Some((&self.frame, *partial));
+                }
+                return Some((self.turn(), *partial));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::display::{
+        panel::Rotation,
+        raster::{FrameBuffer, TextCanvas},
+        Operation, PartialUpdate, Pos,
+    };
+
+    #[test]
+    fn draw_text() {
+        let mut frame = FrameBuffer::new(20, 10);
+        frame.draw_text("|", &Pos(0, 0), 8);
+        //cell is 4 pixels wide so the bar of the 6 column glyph is at x=1
+        assert!((0..7).all(|y| frame.get(1, y)));
+        assert!(!frame.get(1, 7));
+        assert!(!frame.get(0, 3));
+        assert!(!frame.get(2, 3));
+
+        frame.clear();
+        frame.draw_text(" \n|", &Pos(10, -5), 8);
+        assert!(frame.get(11, 3));
+        assert!(!frame.get(11, 2));
+    }
+
+    #[test]
+    fn canvas() {
+        let mut canvas = TextCanvas::new(16, 8);
+        let write = Operation::WriteAll(PartialUpdate(false));
+        canvas.apply(&Operation::AddText(
+            "-".to_string(),
+            Pos(0, 0),
+            8,
+            "a".to_string(),
+        ));
+        canvas.apply(&Operation::AddText(
+            "|".to_string(),
+            Pos(0, 0),
+            8,
+            "a".to_string(),
+        ));
+        assert!(canvas.apply(&write).expect("no frame").0.get(0, 3));
+
+        canvas.apply(&Operation::UpdateText("a".to_string(), " ".to_string()));
+        assert!(canvas.apply(&write).expect("no frame").0.bits().iter().all(|b| *b == 0));
+
+        canvas.apply(&Operation::UpdateText("a".to_string(), "-".to_string()));
+        canvas.apply(&Operation::RemoveText("a".to_string()));
+        assert!(canvas.apply(&write).expect("no frame").0.bits().iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn rotation() {
+        let mut canvas = TextCanvas::new(16, 8);
+        let write = Operation::WriteAll(PartialUpdate(false));
+        canvas.apply(&Operation::AddText(
+            "|".to_string(),
+            Pos(0, 0),
+            8,
+            "a".to_string(),
+        ));
+
+        //drawn down the left of a screen 8 wide and 16 high, the bar
+        //lies along the top right once turned
+        canvas.set_rotation(Rotation::Quarter);
+        let frame = canvas.apply(&write).expect("no frame").0;
+        assert!((0..7).all(|y| frame.get(15 - y, 1)));
+        assert!(!frame.get(8, 1));
+
+        canvas.set_rotation(Rotation::Half);
+        let frame = canvas.apply(&write).expect("no frame").0;
+        assert!((0..7).all(|y| frame.get(14, 7 - y)));
+
+        canvas.set_rotation(Rotation::ThreeQuarters);
+        let frame = canvas.apply(&write).expect("no frame").0;
+        assert!((0..7).all(|y| frame.get(y, 6)));
+    }
+
+    #[test]
+    fn images() {
+        let mut frame = FrameBuffer::new(9, 2);
+        frame.set(0, 0, true);
+        frame.set(8, 1, true);
+
+        let mut pbm = Vec::new();
+        frame.write_pbm(&mut pbm).expect("pbm");
+        assert_eq!(pbm, b"P4\n9 2\n\x80\x00\x00\x80".to_vec());
+
+        let mut png = Vec::new();
+        frame.write_png(&mut png).expect("png");
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[png.len() - 12..], b"\x00\x00\x00\x00IEND\xaeB`\x82");
+    }
+}
@@ -0,0 +1,90 @@
+/*
+Copyright [2019] [Kieran White]
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::display::{
+    panel::{Panel, Rotation},
+    raster::{FrameBuffer, TextCanvas},
+    DisplayBackend, Error, Operation,
+};
+use log::trace;
+use std::{
+    ffi::OsStr,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    slice::Iter,
+};
+
+const PNG_EXTENSION: &str = "png";
+const PARTIAL_EXTENSION: &str = "partial";
+
+/// A display backend that needs no hardware. Every WriteAll operation
+/// renders the screen to an image file, replacing the previous
+/// one. The file is written as a PNG if its name ends in .png and as a
+/// PBM otherwise.
+pub struct Simulator {
+    canvas: TextCanvas,
+    output: PathBuf,
+    panel: Panel,
+}
+
+impl Simulator {
+    pub fn new(output: &Path, panel: Panel) -> Simulator {
+        let (width, height) = panel.pixels();
+        Simulator {
+            canvas: TextCanvas::new(width, height),
+            output: output.to_path_buf(),
+            panel,
+        }
+    }
+
+    fn save(frame: &FrameBuffer, output: &Path) -> Result<(), Error> {
+        //write to a temporary file first so that an image viewer
+        //watching the output never sees a half written image
+        let partial = output.with_extension(PARTIAL_EXTENSION);
+        {
+            let mut writer = BufWriter::new(File::create(&partial)?);
+            if output.extension() == Some(OsStr::new(PNG_EXTENSION)) {
+                frame.write_png(&mut writer)?;
+            } else {
+                frame.write_pbm(&mut writer)?;
+            }
+            writer.flush()?;
+        }
+        fs::rename(&partial, output)?;
+        trace!("simulator wrote {:?}", output);
+        Ok(())
+    }
+}
+
+impl DisplayBackend for Simulator {
+    fn send(&mut self, els: Iter<Operation>, _sync: bool) -> Result<(), Error> {
+        for el in els {
+            if let Some((frame, _partial)) = self.canvas.apply(el) {
+                Simulator::save(frame, &self.output)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn panel(&self) -> Option<Panel> {
+        Some(self.panel)
+    }
+
+    fn rotate(&mut self, rotation: Rotation) {
+        self.canvas.set_rotation(rotation);
+    }
+}
@@ -26,7 +26,7 @@ use log::trace;
 use LongPressMachine::*;
 
 const BLOCK_SIZE: usize = 4 * 1024;
-const PIN_COUNT: usize = 28;
+pub const PIN_COUNT: usize = 28;
 const READ_REG_OFFSET: usize = 13;
 
 pub const SW1_GPIO: usize = 16;
@@ -40,7 +40,14 @@ err!(Error {
 });
 
 pub trait Button<E> {
-    fn event(&mut self, ports: &mut GPIO) -> Result<Option<E>, Error>;
+    fn event(&mut self, ports: &mut dyn Pins) -> Result<Option<E>, Error>;
+}
+
+/// Source of the input levels that drive Buttons.
+pub trait Pins {
+    /// Returns true if the button attached to the pin is pressed,
+    /// along with how long it has been in that state.
+    fn pinin(&mut self, gpio: Pin) -> Result<(bool, Duration), Error>;
 }
 
 stm!(machine button_stm, LongPressMachine, LongPressAtEnd, LongPressTerminals, [ReleasePending, PressedPending, LongPressed] => NotPressed() |end|, {
@@ -126,7 +133,7 @@ impl LongPressButton {
 }
 
 impl Button<LongButtonEvent> for LongPressButton {
-    fn event(&mut self, ports: &mut GPIO) -> Result<Option<LongButtonEvent>, Error> {
+    fn event(&mut self, ports: &mut dyn Pins) -> Result<Option<LongButtonEvent>, Error> {
         let (pressing, duration) = ports.pinin(self.pin)?;
         let mut event: Option<LongButtonEvent> = None;
         let mut state = self.state.take().expect("state of LongPressButton is not initialised");
@@ -269,7 +276,10 @@ impl GPIO {
         }
     }
 
-    pub fn pinin(&mut self, gpio: Pin) -> Result<(bool, Duration), Error> {
+}
+
+impl Pins for GPIO {
+    fn pinin(&mut self, gpio: Pin) -> Result<(bool, Duration), Error> {
         let pin_num = gpio.0;
 
         if pin_num >= PIN_COUNT {
@@ -287,3 +297,26 @@ impl GPIO {
         Ok((new_val, self.snap[pin_num].1.elapsed()))
     }
 }
+
+/// Pins for a machine without buttons attached, such as when the
+/// display is simulated. No button is ever pressed.
+pub struct Disconnected {
+    since: Instant,
+}
+
+impl Disconnected {
+    pub fn new() -> Disconnected {
+        Disconnected {
+            since: Instant::now(),
+        }
+    }
+}
+
+impl Pins for Disconnected {
+    fn pinin(&mut self, gpio: Pin) -> Result<(bool, Duration), Error> {
+        if gpio.0 >= PIN_COUNT {
+            return Err(Error::InvalidPin(gpio));
+        }
+        Ok((false, self.since.elapsed()))
+    }
+}
@@ -22,29 +22,49 @@ limitations under the License.
 #[macro_use]
 mod cal_display;
 mod cal_machine;
+mod cli;
 mod display;
 mod err;
 mod formatter;
 mod gpio_in;
 #[macro_use]
 mod new_type;
+mod settings;
 mod stm;
 #[allow(bare_trait_objects)]
 mod systemd1;
+#[cfg(test)]
+mod temp_dir;
 //mod yielder;
 
 use cal_display::{Error as CalDisplayError, Renderer};
-use cal_machine::{Error as CalMachineError, RefreshToken};
+use cal_machine::{
+    cache::{EventCache, CACHE_FILE, DEFAULT_CACHE_DIR},
+    load_settings,
+    sources::Calendars,
+    terminal, Error as CalMachineError, Persisted, Signals,
+};
+use chrono::prelude::*;
+use cli::{Command as CliCommand, Options, USAGE};
 use dbus::{BusType, Connection};
-use display::Error as DisplayError;
+use display::{
+    epd::{Epd, DEFAULT_EPD_DIR},
+    simulator::Simulator,
+    Error as DisplayError,
+};
+use gpio_in::{Disconnected, Error as GPIOError, Pins, GPIO};
+use settings::{Settings, SETTINGS_FILE};
 use log::{trace,error};
-use nix::{mount::*, unistd::*, Error as NixError};
+use nix::{
+    mount::*,
+    sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal},
+    Error as NixError,
+};
 use std::{
     env::{self, var_os},
-    ffi::CString,
     fs::{self, create_dir_all},
     io,
-    os::unix::fs::symlink,
+    os::unix::fs::{symlink, MetadataExt},
     path::{Path, PathBuf},
     process::{self, Command},
     sync::atomic::{AtomicBool, Ordering as AtomicOrdering},
@@ -79,6 +99,7 @@ err!(
         NixError(NixError),
         IOError(io::Error),
         DBus(dbus::Error),
+        GPIO(GPIOError),
         Path(PathError)
     }
 );
@@ -96,7 +117,6 @@ fn installation<'a>(
     version: &str,
 ) -> Result<(), Error> {
     let package_install_dir = &install_dir.join(PKG_NAME);
-    let script_rel_path: &Path = &Path::new(SCRIPTS_DIR).join(Path::new(SCRIPT_NAME));
     let systemd_rel_path: &Path =
         &Path::new(SYSTEMD_DIR).join(Path::new(CALENDAR_MIRROR_UNIT_NAME));
     let exe_link: &Path = Path::new("/proc/self/exe");
@@ -129,8 +149,7 @@ fn installation<'a>(
         }
     }
 
-    let script_path = project_dir.join(script_rel_path);
-    let script_name = Path::new(SCRIPT_NAME);
+    let script_name = Path::new(LEGACY_SCRIPT_NAME);
     let systemd_path = project_dir.join(systemd_rel_path);
     let unit_name = Path::new(CALENDAR_MIRROR_UNIT_NAME);
 
@@ -204,13 +223,10 @@ fn installation<'a>(
             version_path, bin_path
         );
         trace!(
-            "copying exe: from {:?} to {:?}. script: from {:?} to {:?}, unit: from {:?} to {:?} ",
-            exe_path, version_exe, script_path, version_script, systemd_path, version_unit
-        );
-        trace!(
-            "links: exe {:?} script {:?}",
-            runnable_exe_path, runnable_script_path
+            "copying exe: from {:?} to {:?}. unit: from {:?} to {:?} ",
+            exe_path, version_exe, systemd_path, version_unit
         );
+        trace!("links: exe {:?}", runnable_exe_path);
 
         create_dir_all(&version_path)?;
         create_dir_all(&bin_path)?;
@@ -219,16 +235,11 @@ fn installation<'a>(
             fs::copy(&exe_path, &version_exe)?;
         }
 
-        if script_path != version_script {
-            fs::copy(&script_path, &version_script)?;
-        }
-
         if systemd_path != version_unit {
             fs::copy(&systemd_path, &version_unit)?;
         }
 
         symlink(&version_exe, &runnable_exe_path)?;
-        symlink(&version_script, &runnable_script_path)?;
 
         trace!("linking the unit {:?}", version_unit);
         if let Some(version_unit_str) = version_unit.to_str() {
@@ -249,9 +260,9 @@ fn installation<'a>(
     Ok(())
 }
 
-const SCRIPTS_DIR: &str = "scripts";
 const SYSTEMD_DIR: &str = "systemd";
-const SCRIPT_NAME: &str = "calendar_mirror_server.py";
+//installed by versions that rendered through a python server, removed on uninstall
+const LEGACY_SCRIPT_NAME: &str = "calendar_mirror_server.py";
 const CALENDAR_MIRROR_UNIT_NAME: &str = "calendar_mirror.service";
 const NTP_UNIT_NAME: &str = "ntp.service";
 const UNIT_STOP_START_CONFIG: &str = "replace";
@@ -261,9 +272,15 @@ const DEFAULT_VAR_DIR: &str = ".";
 const VAR_DIR_FS_TYPE: &str = "ext4";
 const CALENDAR_MIRROR_VAR: &str = "CALENDAR_MIRROR_VAR";
 const CALENDAR_MIRROR_DEV: &str = "CALENDAR_MIRROR_DEV";
+const CALENDAR_MIRROR_SIM: &str = "CALENDAR_MIRROR_SIM";
+const CALENDAR_MIRROR_EPD: &str = "CALENDAR_MIRROR_EPD";
+const CALENDAR_MIRROR_CACHE: &str = "CALENDAR_MIRROR_CACHE";
 const NETWORK_CHECK_POLL_PERIOD: Duration = Duration::from_millis(750);
 
-fn sync_time<'a>(system_d: &'a dbus::ConnPath<'a, &'a dbus::Connection>) -> Result<(), Error> {
+fn sync_time<'a>(
+    system_d: &'a dbus::ConnPath<'a, &'a dbus::Connection>,
+    time_server: &str,
+) -> Result<(), Error> {
     match system_d.stop_unit(NTP_UNIT_NAME, UNIT_STOP_START_CONFIG) {
         Ok(_) => {
             trace!("stopped the unit {:?}", NTP_UNIT_NAME);
@@ -279,7 +296,7 @@ fn sync_time<'a>(system_d: &'a dbus::ConnPath<'a, &'a dbus::Connection>) -> Resu
     loop {
         let output = Command::new("sntp")
             .arg("-S")
-            .arg("0.us.pool.ntp.org")
+            .arg(time_server)
             .output()?;
         trace!("status: {}", output.status);
         trace!("stdout: {}", String::from_utf8_lossy(&output.stdout));
@@ -295,154 +312,361 @@ fn sync_time<'a>(system_d: &'a dbus::ConnPath<'a, &'a dbus::Connection>) -> Resu
     Ok(())
 }
 
-fn main() -> Result<(), Error> {
-    env_logger::init();
-    let path_opt = var_os("PATH");
-    let paths = if let Some(ref val) = path_opt {
-        val.clone().into_string().expect("invalid path")
-    } else {
-        "".to_string()
-    };
-    trace!("path: {}", paths);
+/// The configuration partition, mounted at the var dir. It's kept read
+/// only except while something is being saved to it.
+struct VarDir<'a> {
+    path: &'a Path,
+    device: Option<&'a Path>,
+    mounted_here: bool,
+    ro_flags: MsFlags,
+    rw_flags: MsFlags,
+}
 
-    let dbus = Connection::get_private(BusType::System)?;
-    let system_d = system_d_inst(&dbus)?;
-    let args: Vec<String> = env::args().collect();
-    let dest_base: &Path = Path::new("/opt/");
-    for arg in args.iter() {
-        match arg.as_str() {
-            "--install" => {
-                installation(&system_d, PackageAction::Install, dest_base, VERSION)?;
-                process::exit(0);
+impl<'a> VarDir<'a> {
+    /// Mounts device at path, unless it's already mounted there by the
+    /// running unit. Without a device path is an ordinary directory.
+    fn mount(path: &'a Path, device: Option<&'a Path>) -> Result<VarDir<'a>, Error> {
+        let var_dir_fs_type: &Path = Path::new(VAR_DIR_FS_TYPE);
+        let mut base_flags = MsFlags::empty();
+        base_flags.insert(MsFlags::MS_NOATIME);
+        base_flags.insert(MsFlags::MS_NOSUID);
+        base_flags.insert(MsFlags::MS_NODEV);
+
+        let mut ro_flags = base_flags;
+        ro_flags.insert(MsFlags::MS_RDONLY);
+
+        let mut mounted_here = false;
+        if let Some(var_dir_dev) = device {
+            create_dir_all(path)?;
+            if is_mount_point(path)? {
+                trace!("already mounted: {:?}", path.display());
+            } else {
+                trace!(
+                    "before mount: {:?} flags: {:?} dev: {:?} fs type: {:?}",
+                    path.display(),
+                    ro_flags,
+                    var_dir_dev,
+                    var_dir_fs_type
+                );
+                mount(
+                    Option::<&Path>::Some(var_dir_dev),
+                    path,
+                    Option::<&Path>::Some(var_dir_fs_type),
+                    ro_flags,
+                    Option::<&Path>::None,
+                )?;
+                trace!("after mount");
+                mounted_here = true;
+            }
+        }
+        base_flags.insert(MsFlags::MS_REMOUNT);
+        ro_flags.insert(MsFlags::MS_REMOUNT);
+
+        Ok(VarDir {
+            path,
+            device,
+            mounted_here,
+            ro_flags,
+            rw_flags: base_flags,
+        })
+    }
+
+    fn remount(&self, flags: MsFlags) -> Result<(), NixError> {
+        mount(
+            Option::<&Path>::None,
+            self.path,
+            Option::<&Path>::None,
+            flags,
+            Option::<&Path>::None,
+        )
+    }
+
+    fn write(&self, persisted: &Persisted) -> io::Result<()> {
+        match persisted {
+            Persisted::RefreshToken(refresh_token) => {
+                refresh_token.save(&self.path.join(cal_machine::REFRESH_TOKEN_FILE))
             }
-            "--uninstall" => {
-                installation(&system_d, PackageAction::Uninstall, &dest_base, VERSION)?;
-                process::exit(0);
+            Persisted::Events(cache) => cache.save(&self.path.join(CACHE_FILE)),
+        }
+    }
+
+    /// Saves to the partition, remounting it read-write while doing so.
+    fn save(&self, persisted: &Persisted) -> Result<(), CalMachineError> {
+        if self.device.is_some() {
+            println!("remounting rw and saving");
+            self.remount(self.rw_flags)?;
+            let saved = self.write(persisted);
+            self.remount(self.ro_flags)?;
+            println!("remounting ro");
+            saved?;
+        } else {
+            println!("saving");
+            self.write(persisted)?;
+        }
+        Ok(())
+    }
+
+    /// Unmounts the partition if it was mounted by VarDir::mount().
+    fn unmount(&self) -> Result<(), Error> {
+        if self.mounted_here {
+            trace!("before umount: {:?}", self.path.display());
+            umount(self.path)?;
+            trace!("after umount");
+        }
+        Ok(())
+    }
+}
+
+fn is_mount_point(path: &Path) -> io::Result<bool> {
+    Ok(fs::metadata(path)?.dev() != fs::metadata(path.join(".."))?.dev())
+}
+
+static RELOAD_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn request_reload(_: nix::libc::c_int) {
+    RELOAD_REQUESTED.store(true, AtomicOrdering::SeqCst);
+}
+
+/// Leaves message on the display until the service is stopped or
+/// reloaded with settings and calendars that can be used, rather than
+/// exiting only to be restarted with the same problem. Those settings
+/// and calendars are returned, or None if the service was stopped.
+fn display_until_fixed(
+    renderer: &mut Renderer,
+    message: &str,
+    var_dir: &Path,
+    quitter: &AtomicBool,
+) -> Result<Option<(Settings, Calendars)>, Error> {
+    renderer.display_error(message)?;
+    while !quitter.load(AtomicOrdering::SeqCst) {
+        if RELOAD_REQUESTED.swap(false, AtomicOrdering::SeqCst) {
+            match load_settings(var_dir) {
+                Ok(loaded) => {
+                    println!("reloaded {}", SETTINGS_FILE);
+                    return Ok(Some(loaded));
+                }
+                Err(message) => {
+                    error!("still unusable: {}", message);
+                    renderer.display_error(&message)?;
+                }
             }
-            _ => {}
         }
+        thread::sleep(NETWORK_CHECK_POLL_PERIOD);
     }
+    Ok(None)
+}
 
+/// Shows the calendars until the unit is stopped, starting with the
+/// settings and calendars loaded from the partition, or the reason
+/// they couldn't be.
+fn mirror<'a>(
+    renderer: &mut Renderer,
+    gpio: &mut dyn Pins,
+    system_d: Option<&'a dbus::ConnPath<'a, &'a dbus::Connection>>,
+    partition: &VarDir,
+    loaded: Result<(Settings, Calendars), String>,
+) -> Result<(), Error> {
+    let var_dir = partition.path;
     let quitter = Arc::new(AtomicBool::new(false));
+    let child_quitter = Arc::clone(&quitter);
+    ctrlc::set_handler(move || {
+        child_quitter.store(true, AtomicOrdering::SeqCst);
+    })
+    .expect("Error setting Ctrl-C handler");
+    //replaces the termination handler ctrlc installed for SIGHUP
+    let reload_action = SigAction::new(
+        SigHandler::Handler(request_reload),
+        SaFlags::SA_RESTART,
+        SigSet::empty(),
+    );
+    unsafe { sigaction(Signal::SIGHUP, &reload_action) }.expect("Error setting SIGHUP handler");
+    let signals = Signals {
+        quit: &quitter,
+        reload: &RELOAD_REQUESTED,
+    };
 
-    if cfg!(feature = "render_stm") {
-        cal_machine::render_stms()?;
-    } else {
+    //calendars read along with the settings, to be used by the next run
+    let (mut settings, mut loaded) = match loaded {
+        Ok((settings, calendars)) => (settings, Some(calendars)),
+        Err(message) => {
+            error!("settings: {}", message);
+            match display_until_fixed(renderer, &message, var_dir, &quitter)? {
+                Some((settings, calendars)) => (settings, Some(calendars)),
+                None => return Ok(()),
+            }
+        }
+    };
+    renderer.set_rotation(settings.rotation());
+    renderer.set_layout(settings.layout(renderer.panel()));
+    if let Some(system_d) = system_d {
         trace!("before sync time");
-        sync_time(&system_d)?;
+        sync_time(system_d, &settings.time_server)?;
         trace!("after sync time");
+    }
 
-        match fork().expect("fork failed") {
-            ForkResult::Parent { child: _ } => {
-                let child_quitter = Arc::clone(&quitter);
-                println!("parent is waiting for child to start server...");
-                let mut renderer = Renderer::wait_for_server()?;
-                ctrlc::set_handler(move || {
-                    child_quitter.store(true, AtomicOrdering::SeqCst);
-                })
-                .expect("Error setting Ctrl-C handler");
-                renderer.disconnect_quits_server()?;
-
-                let var_dir_opt = var_os(CALENDAR_MIRROR_VAR);
-                let var_dir_os = &var_dir_opt.clone().unwrap_or(DEFAULT_VAR_DIR.into());
-                let var_dir: &Path = Path::new(var_dir_os);
-
-                let var_dir_fs_type: &Path = Path::new(VAR_DIR_FS_TYPE);
-                let mut base_flags = MsFlags::empty();
-                base_flags.insert(MsFlags::MS_NOATIME);
-                base_flags.insert(MsFlags::MS_NOSUID);
-                base_flags.insert(MsFlags::MS_NODEV);
-
-                let mut ro_flags = base_flags.clone();
-                ro_flags.insert(MsFlags::MS_RDONLY);
-
-                if var_dir_opt.is_some() {
-                    let var_dir_dev_opt = var_os(CALENDAR_MIRROR_DEV);
-                    let var_dir_dev_os= &var_dir_dev_opt.clone().expect(format!("If the var mount point is specified by the environment so too must a block device using the {} environment variable", CALENDAR_MIRROR_DEV).as_str());
-                    let var_dir_dev: &Path = Path::new(var_dir_dev_os);
-
-                    create_dir_all(var_dir)?;
-                    trace!(
-                        "before mount: {:?} flags: {:?} dev: {:?} fs type: {:?}",
-                        var_dir.display(),
-                        ro_flags,
-                        var_dir_dev,
-                        var_dir_fs_type
-                    );
-                    mount(
-                        Option::<&Path>::Some(var_dir_dev),
-                        var_dir,
-                        Option::<&Path>::Some(var_dir_fs_type),
-                        ro_flags,
-                        Option::<&Path>::None,
-                    )?;
-                    trace!("after mount");
-                }
-                base_flags.insert(MsFlags::MS_REMOUNT);
-                let rw_flags = base_flags;
-                ro_flags.insert(MsFlags::MS_REMOUNT);
-                let ro_flags = ro_flags;
-
-                let config_file = var_dir.join(Path::new("refresh.json"));
-
-                let simple_saver = |refresh_token: &RefreshToken, renderer: &mut Renderer| {
-                    renderer.display_save_warning()?;
-                    if var_dir_opt.is_some() {
-                        println!("remounting rw and saving refresh token");
-                        mount(
-                            Option::<&Path>::None,
-                            var_dir,
-                            Option::<&Path>::None,
-                            rw_flags,
-                            Option::<&Path>::None,
-                        )?;
-                        refresh_token.save(&config_file)?;
-                        mount(
-                            Option::<&Path>::None,
-                            var_dir,
-                            Option::<&Path>::None,
-                            ro_flags,
-                            Option::<&Path>::None,
-                        )?;
-                        println!("remounting ro");
-                    } else {
-                        println!("saving refresh token");
-                        refresh_token.save(&config_file)?;
-                    }
-                    Ok(())
-                };
-
-                loop {
-                    match cal_machine::run(&mut renderer, &quitter, &config_file, simple_saver) {
-                        Err(cal_machine::Error::Reqwest(error)) => {
-                            error!("reqwest error: {:?}", error);
-                            thread::sleep(Duration::from_secs(5));
-                        }
-                        Err(error) => {
-                            renderer.clear()?;
-                            return Err(error.into());
-                        }
-                        Ok(()) => {
-                            break;
-                        }
+    let cache_dir_os = var_os(CALENDAR_MIRROR_CACHE).unwrap_or(DEFAULT_CACHE_DIR.into());
+    let snapshot_file = var_dir.join(Path::new(CACHE_FILE));
+    let mut cache = EventCache::load(&Path::new(&cache_dir_os).join(CACHE_FILE), &snapshot_file);
+
+    let simple_saver = |persisted: Persisted, renderer: &mut Renderer| {
+        renderer.display_save_warning()?;
+        partition.save(&persisted)
+    };
+
+    loop {
+        //read afresh for each run as the machine hands the sources to its worker
+        let mut calendars = match loaded.take().map_or_else(|| settings.calendars(), Ok) {
+            Ok(calendars) => calendars,
+            Err(err) => {
+                error!("calendars: {:?}", err);
+                let message = format!("Failed to read the calendars: {}", err);
+                match display_until_fixed(renderer, &message, var_dir, &quitter)? {
+                    Some((reloaded, calendars)) => {
+                        settings = reloaded;
+                        loaded = Some(calendars);
+                        renderer.set_rotation(settings.rotation());
+                        renderer.set_layout(settings.layout(renderer.panel()));
+                        continue;
                     }
+                    None => break,
                 }
+            }
+        };
+        match cal_machine::run(
+            renderer,
+            gpio,
+            &signals,
+            &mut settings,
+            &mut calendars,
+            &mut cache,
+            simple_saver,
+        ) {
+            Err(cal_machine::Error::Reqwest(error)) => {
+                error!("reqwest error: {:?}", error);
+                thread::sleep(Duration::from_secs(5));
+            }
+            Err(error) => {
+                renderer.clear()?;
+                return Err(error.into());
+            }
+            Ok(()) => {
+                break;
+            }
+        }
+    }
 
-                if var_dir_opt.is_some() {
-                    trace!("before umount: {:?}", var_dir.display(),);
-                    umount(var_dir)?;
-                    trace!("after umount");
-                }
+    println!("finishing up");
+    Ok(())
+}
 
-                println!("finishing up");
-            }
-            ForkResult::Child => {
-                println!("child will now start server...");
-                execvp(
-                    &CString::new(SCRIPT_NAME).expect(&format!("Invalid CString: {}", SCRIPT_NAME)),
-                    &[],
-                )?;
+/// The settings used by a command run from a terminal, which gives up
+/// if they can't be.
+fn terminal_settings(var_dir: &Path) -> Settings {
+    Settings::load(var_dir).unwrap_or_else(|err| {
+        eprintln!("{}", err.message());
+        process::exit(1);
+    })
+}
+
+fn check_config(var_dir: &Path) {
+    let settings = terminal_settings(var_dir);
+    if let Err(err) = settings.calendars() {
+        eprintln!("Failed to read the calendars: {}", err);
+        process::exit(1);
+    }
+    println!("{} and the calendars are valid", SETTINGS_FILE);
+}
+
+fn main() -> Result<(), Error> {
+    let options = Options::parse(env::args()).unwrap_or_else(|err| {
+        eprintln!("{}\n\n{}", err.message(), USAGE);
+        process::exit(2);
+    });
+    let mut logger = env_logger::Builder::from_default_env();
+    if let Some(level) = options.log_level {
+        logger.filter_level(level);
+    }
+    logger.init();
+
+    let path_opt = var_os("PATH");
+    let paths = if let Some(ref val) = path_opt {
+        val.clone().into_string().expect("invalid path")
+    } else {
+        "".to_string()
+    };
+    trace!("path: {}", paths);
+
+    let var_dir = options
+        .var_dir
+        .or_else(|| var_os(CALENDAR_MIRROR_VAR).map(PathBuf::from));
+    if options.device.is_some() && var_dir.is_none() {
+        eprintln!("--device needs --var-dir or ${}\n\n{}", CALENDAR_MIRROR_VAR, USAGE);
+        process::exit(2);
+    }
+    let device = options
+        .device
+        .or_else(|| var_os(CALENDAR_MIRROR_DEV).map(PathBuf::from));
+    //a device is only mounted where the var dir has been given
+    let device = device.filter(|_| var_dir.is_some());
+    let var_dir = var_dir.unwrap_or_else(|| PathBuf::from(DEFAULT_VAR_DIR));
+    let dest_base: &Path = Path::new("/opt/");
+
+    match options.command {
+        CliCommand::Help => print!("{}", USAGE),
+        CliCommand::Install => {
+            let dbus = Connection::get_private(BusType::System)?;
+            installation(&system_d_inst(&dbus)?, PackageAction::Install, dest_base, VERSION)?;
+        }
+        CliCommand::Uninstall => {
+            let dbus = Connection::get_private(BusType::System)?;
+            installation(&system_d_inst(&dbus)?, PackageAction::Uninstall, dest_base, VERSION)?;
+        }
+        CliCommand::Auth => {
+            let partition = VarDir::mount(&var_dir, device.as_deref())?;
+            let authorised = terminal::authorise(&var_dir, |persisted| partition.save(&persisted));
+            partition.unmount()?;
+            authorised?;
+        }
+        CliCommand::Fetch(date) => {
+            let settings = terminal_settings(&var_dir);
+            let date = Local.ymd(date.year(), date.month(), date.day()).and_hms(0, 0, 0);
+            let apps = terminal::fetch(&settings, date)?;
+            terminal::print_day(&date, &apps);
+        }
+        CliCommand::RenderStms => {
+            if !cfg!(feature = "render_stm") {
+                eprintln!("Rebuild with the render_stm feature to render the state machines");
+                process::exit(1);
             }
+            cal_machine::render_stms()?;
+        }
+        CliCommand::CheckConfig => check_config(&var_dir),
+        CliCommand::Run => {
+            //mounted first as the settings say which panel is fitted
+            //when the display can't tell
+            let partition = VarDir::mount(&var_dir, device.as_deref())?;
+            let loaded = load_settings(&var_dir);
+            let panel = loaded
+                .as_ref()
+                .ok()
+                .and_then(|(settings, _)| settings.panel)
+                .unwrap_or_default();
+            let mirrored = if let Some(sim_output) = var_os(CALENDAR_MIRROR_SIM) {
+                println!("simulating display in {:?}", sim_output);
+                let simulator = Simulator::new(Path::new(&sim_output), panel);
+                let mut renderer = Renderer::new(Box::new(simulator))?;
+                mirror(&mut renderer, &mut Disconnected::new(), None, &partition, loaded)
+            } else {
+                let dbus = Connection::get_private(BusType::System)?;
+                let system_d = system_d_inst(&dbus)?;
+                let epd_dir_opt = var_os(CALENDAR_MIRROR_EPD);
+                let epd_dir = Path::new(epd_dir_opt.as_deref().unwrap_or(DEFAULT_EPD_DIR.as_ref()));
+                let mut renderer = Renderer::new(Box::new(Epd::new(epd_dir, panel)))?;
+                let mut gpio = GPIO::new()?;
+                mirror(&mut renderer, &mut gpio, Some(&system_d), &partition, loaded)
+            };
+            partition.unmount()?;
+            mirrored?;
         }
     }
     Ok(())
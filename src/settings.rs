@@ -0,0 +1,350 @@
+/*
+Copyright [2019] [Kieran White]
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::{
+    cal_display::{Layout, Placement, View},
+    cal_machine::sources::{Calendars, CalendarsConfig},
+    display::panel::{Panel, Rotation},
+    err,
+    gpio_in::{PIN_COUNT, SW1_GPIO, SW2_GPIO, SW3_GPIO, SW4_GPIO},
+};
+use serde::Deserialize;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+pub const SETTINGS_FILE: &str = "calendar_mirror.toml";
+const CALENDARS_FILE: &str = "calendars.json";
+
+/// A setting whose value can't be used, described for the display.
+#[derive(Debug)]
+pub struct InvalidError(pub String);
+
+err!(Error {
+    IO(io::Error),
+    Toml(toml::de::Error),
+    Invalid(InvalidError)
+});
+
+impl Error {
+    pub fn message(&self) -> String {
+        match self {
+            Error::IO(err) => format!("Failed to read {}: {}", SETTINGS_FILE, err),
+            Error::Toml(err) => format!("Invalid {}: {}", SETTINGS_FILE, err),
+            Error::Invalid(InvalidError(msg)) => format!("Invalid {}: {}", SETTINGS_FILE, msg),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Timing {
+    /// How often the events are downloaded again.
+    pub recheck_secs: u64,
+    /// How often the current time is moved along the events.
+    pub time_update_secs: u64,
+    pub button_poll_millis: u64,
+    /// How long a button must be held for a long press.
+    pub long_press_millis: u64,
+    /// How long after changing the date the new date's events are read.
+    pub release_millis: u64,
+}
+
+impl Default for Timing {
+    fn default() -> Timing {
+        Timing {
+            recheck_secs: 300,
+            time_update_secs: 60,
+            button_poll_millis: 25,
+            long_press_millis: 4000,
+            release_millis: 1500,
+        }
+    }
+}
+
+impl Timing {
+    pub fn recheck_period(&self) -> Duration {
+        Duration::from_secs(self.recheck_secs)
+    }
+
+    pub fn time_update_period(&self) -> Duration {
+        Duration::from_secs(self.time_update_secs)
+    }
+
+    pub fn button_poll_period(&self) -> Duration {
+        Duration::from_millis(self.button_poll_millis)
+    }
+
+    pub fn long_duration(&self) -> Duration {
+        Duration::from_millis(self.long_press_millis)
+    }
+
+    pub fn longish_duration(&self) -> Duration {
+        Duration::from_millis(self.release_millis)
+    }
+}
+
+/// The GPIO pin of each control button.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Buttons {
+    pub next: usize,
+    pub scroll: usize,
+    pub reset: usize,
+    pub back: usize,
+    /// The number of lines moved by each press of the scroll button.
+    pub scroll_rows: usize,
+}
+
+impl Default for Buttons {
+    fn default() -> Buttons {
+        Buttons {
+            next: SW1_GPIO,
+            scroll: SW2_GPIO,
+            reset: SW3_GPIO,
+            back: SW4_GPIO,
+            scroll_rows: 5,
+        }
+    }
+}
+
+/// Changes to the events screen of the panel's layout profile.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LayoutOverrides {
+    pub columns: Option<usize>,
+    pub rows: Option<usize>,
+    pub heading: Option<Placement>,
+    pub pulse: Option<Placement>,
+    pub email: Option<Placement>,
+    pub events: Option<Placement>,
+}
+
+impl LayoutOverrides {
+    fn apply(&self, profile: Layout) -> Layout {
+        Layout {
+            columns: self.columns.unwrap_or(profile.columns),
+            rows: self.rows.unwrap_or(profile.rows),
+            heading: self.heading.unwrap_or(profile.heading),
+            pulse: self.pulse.unwrap_or(profile.pulse),
+            email: self.email.unwrap_or(profile.email),
+            events: self.events.unwrap_or(profile.events),
+            ..profile
+        }
+    }
+}
+
+/// Everything that can be tuned without recompiling. Each section and
+/// setting is optional, those left out keep their defaults.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// The NTP server the clock is set from on startup.
+    pub time_server: String,
+    pub timing: Timing,
+    pub buttons: Buttons,
+    /// Chooses the layout profile when the display can't tell which
+    /// panel it has.
+    pub panel: Option<Panel>,
+    /// Degrees the screen is turned clockwise: 0, 90, 180 or 270.
+    pub rotation: u32,
+    pub layout: LayoutOverrides,
+    /// How the events are shown on startup: "day", "week", "month" or
+    /// "up_next".
+    pub view: View,
+    /// Replaces calendars.json when present.
+    pub calendars: Option<CalendarsConfig>,
+    /// Where the settings were read from, which is also where the
+    /// credentials are kept.
+    #[serde(skip)]
+    pub var_dir: PathBuf,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            time_server: "0.us.pool.ntp.org".to_string(),
+            timing: Timing::default(),
+            buttons: Buttons::default(),
+            panel: None,
+            rotation: 0,
+            layout: LayoutOverrides::default(),
+            view: View::Day,
+            calendars: None,
+            var_dir: PathBuf::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// A missing file leaves every setting at its default.
+    pub fn load(var_dir: &Path) -> Result<Settings, Error> {
+        let path: PathBuf = var_dir.join(SETTINGS_FILE);
+        let mut settings: Settings = match fs::read_to_string(&path) {
+            Ok(text) => toml::from_str(&text)?,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Settings::default(),
+            Err(err) => return Err(err.into()),
+        };
+        settings.validate()?;
+        settings.var_dir = var_dir.to_path_buf();
+        Ok(settings)
+    }
+
+    pub fn rotation(&self) -> Rotation {
+        Rotation::from_degrees(self.rotation).unwrap_or_default()
+    }
+
+    /// The layout of the panel detected by the display, or if none was
+    /// the one given here, with the changes made here.
+    pub fn layout(&self, detected: Option<Panel>) -> Layout {
+        let panel = detected.or(self.panel).unwrap_or_default();
+        self.layout.apply(Layout::profile(panel, self.rotation()))
+    }
+
+    /// The calendars configured here or, failing that, in calendars.json.
+    pub fn calendars(&self) -> io::Result<Calendars> {
+        match &self.calendars {
+            Some(calendars) => Ok(calendars.calendars()),
+            None => Ok(CalendarsConfig::load(&self.var_dir.join(CALENDARS_FILE))?.calendars()),
+        }
+    }
+
+    fn validate(&self) -> Result<(), InvalidError> {
+        let invalid = |msg: &str| Err(InvalidError(msg.to_string()));
+        let timing = &self.timing;
+        let buttons = &self.buttons;
+        let layout = &self.layout;
+
+        if self.time_server.trim().is_empty() {
+            return invalid("time_server is empty");
+        }
+        if timing.recheck_secs == 0 || timing.time_update_secs == 0 {
+            return invalid("timing periods must be greater than 0");
+        }
+        if timing.button_poll_millis == 0 || timing.button_poll_millis >= timing.release_millis {
+            return invalid("button_poll_millis must be between 0 and release_millis");
+        }
+        if timing.release_millis >= timing.long_press_millis {
+            return invalid("release_millis must be less than long_press_millis");
+        }
+
+        let pins = [buttons.next, buttons.scroll, buttons.reset, buttons.back];
+        if let Some(pin) = pins.iter().find(|pin| **pin >= PIN_COUNT) {
+            return Err(InvalidError(format!(
+                "button pin {} is not below {}",
+                pin, PIN_COUNT
+            )));
+        }
+        if pins.iter().enumerate().any(|(i, pin)| pins[..i].contains(pin)) {
+            return invalid("each button needs a pin of its own");
+        }
+        if buttons.scroll_rows == 0 {
+            return invalid("scroll_rows must be greater than 0");
+        }
+
+        if Rotation::from_degrees(self.rotation).is_none() {
+            return invalid("rotation must be 0, 90, 180 or 270");
+        }
+        if layout.columns == Some(0) || layout.rows == Some(0) {
+            return invalid("the layout needs at least one column and row");
+        }
+        let placements = [layout.heading, layout.pulse, layout.email, layout.events];
+        if placements.iter().flatten().any(|placement| placement.size == 0) {
+            return invalid("font sizes must be greater than 0");
+        }
+
+        if let Some(calendars) = &self.calendars {
+            calendars.validate().map_err(InvalidError)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cal_display::{Layout, View},
+        display::panel::{Panel, Rotation},
+        settings::{Error, Settings},
+    };
+
+    #[test]
+    fn parse_and_validate() {
+        let settings: Settings = toml::from_str(
+            r#"
+            time_server = "pool.ntp.org"
+
+            [timing]
+            recheck_secs = 600
+
+            [layout]
+            rows = 12
+            events = { x = 0, y = 24, size = 14 }
+
+            [calendars]
+            google_calendars = ["primary", "team@example.com"]
+            "#,
+        )
+        .expect("settings");
+        assert_eq!(settings.timing.recheck_secs, 600);
+        assert_eq!(settings.timing.time_update_secs, 60);
+        let layout = settings.layout(None);
+        assert_eq!(layout.rows, 12);
+        assert_eq!(layout.columns, 26);
+        assert_eq!(layout.events.size, 14);
+        assert_eq!(settings.calendars.expect("calendars").google_calendars.len(), 2);
+
+        let clash: Settings = toml::from_str("[buttons]\nnext = 21\nback = 21").expect("buttons");
+        match clash.validate() {
+            Err(err) => assert!(Error::from(err).message().contains("pin of its own")),
+            Ok(()) => panic!("duplicate pins accepted"),
+        }
+        assert!(toml::from_str::<Settings>("[timing]\nrecheck = 1").is_err());
+    }
+
+    #[test]
+    fn panel_selects_layout() {
+        let settings: Settings = toml::from_str("[layout]\nrows = 12").expect("layout");
+        assert_eq!(settings.layout(Some(Panel::Small)).columns, 21);
+        assert_eq!(settings.layout(Some(Panel::Small)).rows, 12);
+
+        let medium: Settings = toml::from_str("panel = \"1.9\"").expect("panel");
+        assert_eq!(medium.layout(None), Layout::profile(Panel::Medium, Rotation::None));
+        assert_eq!(
+            medium.layout(Some(Panel::Wide)),
+            Layout::profile(Panel::Wide, Rotation::None)
+        );
+        assert!(toml::from_str::<Settings>("panel = \"3.1\"").is_err());
+    }
+
+    #[test]
+    fn rotation() {
+        let portrait: Settings = toml::from_str("rotation = 270").expect("rotation");
+        assert_eq!(portrait.layout(None).columns, 19);
+        let tilted: Settings = toml::from_str("rotation = 45").expect("rotation");
+        assert!(tilted.validate().is_err());
+    }
+
+    #[test]
+    fn view() {
+        assert_eq!(Settings::default().view, View::Day);
+        let room: Settings = toml::from_str("view = \"up_next\"").expect("view");
+        assert_eq!(room.view, View::UpNext);
+        assert!(toml::from_str::<Settings>("view = \"year\"").is_err());
+    }
+}